@@ -1,39 +1,261 @@
 use std::path::PathBuf;
 
-use clap::{command, Parser, ValueEnum};
+use clap::{
+    parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum,
+};
 use opt2doc_derive::Opt2Doc;
+use serde::Deserialize;
 
-#[derive(Parser, Debug, Opt2Doc)]
+use crate::{config::ProjectConfig, KeyCase, KeyFormat, KeyStyle};
+
+#[derive(Parser, Debug, Clone, Opt2Doc)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     /// Optional name to operate on
     name: Option<String>,
     /// Repo dir to search for the cargo workspace.
-    #[arg(long, default_value = ".")]
-    #[opt2doc(default = ".")]
+    #[arg(long, global = true, default_value = ".")]
     pub repo: PathBuf,
 
     /// The path output files.
     #[arg(short, long, default_value = "target/opt2doc/")]
-    #[opt2doc(default = "target/opt2doc/", typ = "String")]
+    #[opt2doc(typ = "String")]
     pub output: PathBuf,
 
-    /// Format to render. Options are `Markdown/Toml/Yml/Html`
-    #[arg(short, long, value_enum)]
-    #[opt2doc(default = "None")]
-    pub render: RenderFormat,
+    /// Format to render. Options are `Markdown/Toml/Yml/Html/JsonSchema/Env`, several can be given
+    /// at once, i.e. `markdown,toml`.
+    #[arg(short, long, value_enum, value_delimiter = ',', default_value = "none")]
+    pub render: Vec<RenderFormat>,
 
     /// Name of the root option struct. Setting this will ignore all other options
     /// that are not accessible from the given root.
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub root: Option<Vec<String>>,
 
-    /// The path of config file. E.g., `./opt2doc.toml`.
-    #[arg(short, long)]
+    /// The path of config file. E.g., `./opt2doc.toml`. By default `opt2doc.toml` at the
+    /// workspace root is used if it exists.
+    #[arg(short, long, global = true)]
     pub config: Option<PathBuf>,
+
+    /// Render from a `metadata.json` written by an earlier run instead of collecting metadata,
+    /// so no rust toolchain is needed.
+    #[arg(long, global = true)]
+    pub from_metadata: Option<PathBuf>,
+
+    /// Compare the rendered files with the ones already in `--output` instead of writing them,
+    /// print a diff and exit with a non-zero code if they differ.
+    #[arg(long, overrides_with = "no_check")]
+    pub check: bool,
+
+    /// Write the rendered files even if `check = true` is set in the config file.
+    #[arg(long, overrides_with = "check")]
+    no_check: bool,
+
+    /// How nested keys are written in markdown output, `dotted` (`inner.cfg`) or `env`
+    /// (`APP__INNER__CFG`).
+    #[arg(long, value_enum, default_value = "dotted")]
+    pub key_style: KeyStyle,
+
+    /// Delimiter between the parts of a nested key in markdown output, `.` by default and `__`
+    /// for `--key-style env`.
+    #[arg(long)]
+    pub delimiter: Option<String>,
+
+    /// Put before every key in markdown output, i.e. the name of the root table or the env var
+    /// prefix.
+    #[arg(long)]
+    pub key_prefix: Option<String>,
+
+    /// Put before every environment variable in env output, i.e. `APP` for
+    /// `APP__DATABASE__URL`.
+    #[arg(long)]
+    pub env_prefix: Option<String>,
+
+    /// Case of environment variable names, in env output and with `--key-style env`.
+    #[arg(long, value_enum, default_value = "upper")]
+    pub env_case: KeyCase,
+
+    /// Only document types from these packages.
+    #[arg(short, long, global = true)]
+    pub package: Vec<String>,
+
+    /// Leave out types from these packages.
+    #[arg(long, global = true)]
+    pub exclude: Vec<String>,
+
+    /// How metadata is sent back from `cargo doc`.
+    #[arg(long, global = true, value_enum, default_value = "socket")]
+    pub transport: Transport,
+
+    /// Parse the sources with `syn` instead of running `cargo doc`. Much faster, but types
+    /// generated by other macros are not seen.
+    #[arg(long, global = true, overrides_with = "no_scan")]
+    pub scan: bool,
+
+    /// Run `cargo doc` even if `scan = true` is set in the config file.
+    #[arg(long, global = true, overrides_with = "scan")]
+    no_scan: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
 }
 
-#[derive(Default, Parser, Debug, Clone, ValueEnum)]
+impl Args {
+    /// parse the command line, flags that are not given are taken from the config file, see
+    /// [`ProjectConfig`]
+    pub fn parse_with_config() -> Result<(Self, ProjectConfig), String> {
+        let matches = Self::command().get_matches();
+        let mut args = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        let path = match &args.config {
+            Some(path) => path.clone(),
+            None => match ProjectConfig::discover(&args.repo) {
+                Some(path) => path,
+                None => return Ok((args, ProjectConfig::default())),
+            },
+        };
+        let mut config = ProjectConfig::load(&path)?;
+        args.merge(&matches, &mut config);
+        Ok((args, config))
+    }
+
+    /// how keys are written in markdown output
+    pub fn key_format(&self) -> KeyFormat {
+        KeyFormat {
+            style: self.key_style,
+            delimiter: self.delimiter.clone(),
+            prefix: self.key_prefix.clone(),
+            case: self.env_case,
+        }
+    }
+
+    /// how keys are written as environment variables in env output
+    pub fn env_format(&self) -> KeyFormat {
+        KeyFormat {
+            style: KeyStyle::Env,
+            delimiter: None,
+            prefix: self.env_prefix.clone(),
+            case: self.env_case,
+        }
+    }
+
+    fn merge(&mut self, matches: &ArgMatches, config: &mut ProjectConfig) {
+        let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+        fn fill<T: Clone>(given: bool, arg: &mut T, value: &Option<T>) {
+            if let (false, Some(value)) = (given, value) {
+                *arg = value.clone();
+            }
+        }
+        fill(given("repo"), &mut self.repo, &config.repo);
+        fill(given("output"), &mut self.output, &config.output);
+        fill(given("render"), &mut self.render, &config.render);
+        fill(
+            given("root"),
+            &mut self.root,
+            &config.root.clone().map(Some),
+        );
+        fill(given("transport"), &mut self.transport, &config.transport);
+        fill(
+            given("scan") || given("no_scan"),
+            &mut self.scan,
+            &config.scan,
+        );
+        fill(
+            given("from_metadata"),
+            &mut self.from_metadata,
+            &config.from_metadata.clone().map(Some),
+        );
+        fill(
+            given("check") || given("no_check"),
+            &mut self.check,
+            &config.check,
+        );
+        fill(given("key_style"), &mut self.key_style, &config.key_style);
+        fill(
+            given("delimiter"),
+            &mut self.delimiter,
+            &config.delimiter.clone().map(Some),
+        );
+        fill(
+            given("key_prefix"),
+            &mut self.key_prefix,
+            &config.key_prefix.clone().map(Some),
+        );
+        fill(
+            given("env_prefix"),
+            &mut self.env_prefix,
+            &config.env_prefix.clone().map(Some),
+        );
+        fill(given("env_case"), &mut self.env_case, &config.env_case);
+        fill(given("package"), &mut self.package, &config.package);
+        fill(given("exclude"), &mut self.exclude, &config.exclude);
+        // flags given on the command line apply to every format
+        for options in config.format.values_mut() {
+            if given("output") {
+                options.output = None;
+            }
+            if given("root") {
+                options.root = None;
+            }
+        }
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Validate a config file against the collected metadata, reporting unknown keys,
+    /// deprecated keys, type mismatches and missing required keys.
+    CheckConfig(CheckConfigArgs),
+    /// Compare the documented keys at two git revisions and write the added, removed and
+    /// renamed keys, changed defaults and types and newly deprecated keys as markdown.
+    Diff(DiffArgs),
+    /// Compare the documented keys with a baseline snapshot and fail on breaking changes, i.e.
+    /// removed or renamed keys, narrowed types, changed defaults or new required keys, unless
+    /// the package version has a major bump since the snapshot.
+    Compat(CompatArgs),
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct CompatArgs {
+    /// The baseline snapshot, i.e. `opt2doc-baseline.json`.
+    pub baseline: PathBuf,
+    /// Write the current metadata and package version to the baseline instead of comparing.
+    #[arg(long)]
+    pub update: bool,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct DiffArgs {
+    /// The revision to compare from, i.e. the last release tag.
+    pub from: String,
+    /// The revision to compare to.
+    pub to: String,
+    /// Write the markdown to this file instead of stdout.
+    #[arg(long)]
+    pub out: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug, Clone)]
+pub struct CheckConfigArgs {
+    /// The config file to check, its format is picked by extension: `toml/yaml/yml/json`.
+    pub file: PathBuf,
+}
+
+#[derive(
+    Default,
+    Parser,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    ValueEnum,
+    Deserialize,
+    Opt2Doc,
+)]
+#[serde(rename_all = "kebab-case")]
 pub enum RenderFormat {
     /// Do nothing. Only the JSON metadata file will be generated.
     #[default]
@@ -46,5 +268,37 @@ pub enum RenderFormat {
     Yaml,
     /// Render a single-page HTML file with all options.
     Html,
+    /// Render a JSON Schema for editor completion and validation of config files.
+    JsonSchema,
+    /// Render a markdown table of the environment variables of all options, and a commented
+    /// `.env.example` file.
+    Env,
     // TODO: support more formats
 }
+
+impl RenderFormat {
+    /// extension of the rendered files
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RenderFormat::None => "",
+            RenderFormat::Markdown => "md",
+            RenderFormat::Toml => "toml",
+            RenderFormat::Yaml => "yaml",
+            RenderFormat::Html => "html",
+            RenderFormat::JsonSchema => "json",
+            // the `.env.example` file is named by the renderer
+            RenderFormat::Env => "env.md",
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, ValueEnum, Deserialize, Opt2Doc)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transport {
+    /// Send metadata over TCP, the address is set by `OPT2DOC_URL`.
+    #[default]
+    Socket,
+    /// Append metadata to a JSON Lines file unique to each run,
+    /// so concurrent runs never collide.
+    Jsonl,
+}
@@ -0,0 +1,57 @@
+//! The collected metadata as a versioned JSON file, so docs can be rendered later or elsewhere,
+//! i.e. without a rust toolchain.
+
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::CompsiteMetadata;
+
+/// file name in `--output`
+pub const METADATA_FILE_NAME: &str = "metadata.json";
+
+/// bumped whenever a change to [`CompsiteMetadata`] can't be read by older versions
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Contents of `metadata.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataFile {
+    pub schema_version: u32,
+    /// version of `opt2doc` that wrote the file
+    pub tool_version: String,
+    /// sorted by qualified name, see [`CompsiteMetadata::package`] for where each came from
+    pub items: Vec<CompsiteMetadata>,
+}
+
+impl MetadataFile {
+    pub fn new(mut items: Vec<CompsiteMetadata>) -> Self {
+        items.sort_by_key(CompsiteMetadata::qualified_name);
+        Self {
+            schema_version: SCHEMA_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            items,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let file: Self = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+        if file.schema_version > SCHEMA_VERSION {
+            return Err(format!(
+                "{} has schema version {}, written by opt2doc {}, this version only reads up to {}",
+                path.display(),
+                file.schema_version,
+                file.tool_version,
+                SCHEMA_VERSION
+            ));
+        }
+        Ok(file)
+    }
+
+    /// contents of `metadata.json`, written along with the rendered files
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap() + "\n"
+    }
+}
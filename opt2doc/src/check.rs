@@ -0,0 +1,505 @@
+//! Validate a user's config file against the collected metadata, i.e. catch misspelled keys
+//! before deploy.
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use serde_json::Value;
+
+use crate::{
+    render::{build_tree, is_optional, is_required, value_kind, ConfigNode, NodeKind, ValueKind},
+    Collection, CompsiteMetadata, FieldMetadata,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// One problem found in the config file, `path` is the dotted key path
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "\u{001b}[1;31merror\u{001b}[0m",
+            Severity::Warning => "\u{001b}[1;93mwarning\u{001b}[0m",
+        };
+        write!(f, "{}: `{}`: {}", severity, self.path, self.message)
+    }
+}
+
+/// Parse a config file, its format is picked by extension: `toml`, `yaml`/`yml` or `json`
+pub fn load_config_file(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let parsed = match extension.as_str() {
+        "toml" => toml::from_str(&content).map_err(|e| e.to_string()),
+        "yaml" | "yml" => serde_yaml::from_str(&content).map_err(|e| e.to_string()),
+        "json" => serde_json::from_str(&content).map_err(|e| e.to_string()),
+        other => Err(format!(
+            "unsupported config file extension `{}`, expected toml, yaml, yml or json",
+            other
+        )),
+    };
+    parsed.map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// check `config` against the keys documented for `root`
+pub fn check_config(
+    root: &CompsiteMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+    config: &Value,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let scope = Scope {
+        path: Vec::new(),
+        nodes: &build_tree(root, items),
+        parent: None,
+    };
+    check_table(&[], &scope, config, &mut diagnostics);
+    diagnostics
+}
+
+/// A table being checked, recursive nodes are checked against one of these
+struct Scope<'a> {
+    /// dotted path of the keys inside, see [`ConfigNode::inner_path`]
+    path: Vec<String>,
+    nodes: &'a [ConfigNode<'a>],
+    parent: Option<&'a Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    fn find(&'a self, path: &[String]) -> Option<&'a Scope<'a>> {
+        if self.path == path {
+            Some(self)
+        } else {
+            self.parent?.find(path)
+        }
+    }
+}
+
+/// `path` is where `value` is in the config file, used in diagnostics
+fn check_table(path: &[String], scope: &Scope, value: &Value, out: &mut Vec<Diagnostic>) {
+    let nodes = scope.nodes;
+    let Value::Object(map) = value else {
+        out.push(Diagnostic {
+            severity: Severity::Error,
+            path: display_path(path),
+            message: format!("expected a table, found {}", value_type_name(value)),
+        });
+        return;
+    };
+
+    for (key, inner) in map {
+        let mut inner_path = path.to_vec();
+        inner_path.push(key.clone());
+        match nodes.iter().find(|node| &node.key == key) {
+            Some(node) => check_node(&inner_path, node, scope, inner, out),
+            None => {
+                let mut message = "unknown key".to_string();
+                if let Some(suggestion) = did_you_mean(key, nodes.iter().map(|n| n.key.as_str())) {
+                    message.push_str(&format!(", did you mean `{}`?", suggestion));
+                }
+                out.push(Diagnostic {
+                    severity: Severity::Error,
+                    path: display_path(&inner_path),
+                    message,
+                });
+            }
+        }
+    }
+
+    for node in nodes {
+        if !map.contains_key(&node.key) && is_required(node.field) {
+            let mut inner_path = path.to_vec();
+            inner_path.push(node.key.clone());
+            out.push(Diagnostic {
+                severity: Severity::Error,
+                path: display_path(&inner_path),
+                message: "missing required key".to_string(),
+            });
+        }
+    }
+}
+
+fn check_node(
+    path: &[String],
+    node: &ConfigNode,
+    scope: &Scope,
+    value: &Value,
+    out: &mut Vec<Diagnostic>,
+) {
+    if let Some(deprecation) = node.field.deprecation() {
+        out.push(Diagnostic {
+            severity: Severity::Warning,
+            path: display_path(path),
+            message: deprecation,
+        });
+    }
+    // `key: ~` in yaml or `null` in json leave an optional key unset
+    if is_optional(node.field) && value.is_null() {
+        return;
+    }
+    match &node.kind {
+        NodeKind::Table {
+            children,
+            collection,
+            ..
+        } => {
+            let inner = Scope {
+                path: node.inner_path(&scope.path),
+                nodes: children,
+                parent: Some(scope),
+            };
+            check_tables(path, *collection, &inner, value, out);
+        }
+        NodeKind::Recursive {
+            collection, target, ..
+        } => {
+            if let Some(target) = scope.find(target) {
+                check_tables(path, *collection, target, value, out);
+            }
+        }
+        NodeKind::Value {
+            enum_item: Some(enum_item),
+        } => {
+            let allowed = enum_item.allowed_values_for(node.field);
+            // data carrying variants are written as a table keyed by the variant name
+            let variant = match value {
+                Value::String(s) => Some(s.clone()),
+                Value::Object(map) if map.len() == 1 => map.keys().next().cloned(),
+                _ => None,
+            };
+            match variant {
+                Some(variant) if allowed.contains(&variant) => {}
+                Some(variant) => {
+                    let mut message = format!("unknown variant `{}`", variant);
+                    if let Some(suggestion) =
+                        did_you_mean(&variant, allowed.iter().map(String::as_str))
+                    {
+                        message.push_str(&format!(", did you mean `{}`?", suggestion));
+                    }
+                    out.push(Diagnostic {
+                        severity: Severity::Error,
+                        path: display_path(path),
+                        message,
+                    });
+                }
+                None => out.push(Diagnostic {
+                    severity: Severity::Error,
+                    path: display_path(path),
+                    message: format!("expected one of {}", allowed.join(", ")),
+                }),
+            }
+        }
+        NodeKind::Value { enum_item: None } => {
+            let kind = value_kind(node.field);
+            let matches = match kind {
+                ValueKind::Bool => value.is_boolean(),
+                ValueKind::Integer => value.is_i64() || value.is_u64(),
+                ValueKind::Float => value.is_number(),
+                // anything unknown is also a `String`, so only check types known to be strings
+                ValueKind::String => !is_string_type(node.field) || value.is_string(),
+                ValueKind::Array => value.is_array(),
+                ValueKind::Map => value.is_object(),
+            };
+            if !matches {
+                out.push(Diagnostic {
+                    severity: Severity::Error,
+                    path: display_path(path),
+                    message: format!(
+                        "expected {} (`{}`), found {}",
+                        kind_name(kind),
+                        node.field.ty,
+                        value_type_name(value)
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// a single table, or a list or map of them, all with the keys in `scope`
+fn check_tables(
+    path: &[String],
+    collection: Option<Collection>,
+    scope: &Scope,
+    value: &Value,
+    out: &mut Vec<Diagnostic>,
+) {
+    match (collection, value) {
+        (None, _) => check_table(path, scope, value, out),
+        (Some(Collection::List), Value::Array(elements)) => {
+            for (i, element) in elements.iter().enumerate() {
+                let mut element_path = path.to_vec();
+                if let Some(last) = element_path.last_mut() {
+                    last.push_str(&format!("[{}]", i));
+                }
+                check_table(&element_path, scope, element, out);
+            }
+        }
+        (Some(Collection::Map), Value::Object(entries)) => {
+            for (name, entry) in entries {
+                let mut entry_path = path.to_vec();
+                entry_path.push(name.clone());
+                check_table(&entry_path, scope, entry, out);
+            }
+        }
+        (Some(collection), _) => out.push(Diagnostic {
+            severity: Severity::Error,
+            path: display_path(path),
+            message: format!(
+                "expected {}, found {}",
+                match collection {
+                    Collection::List => "an array",
+                    Collection::Map => "a table",
+                },
+                value_type_name(value)
+            ),
+        }),
+    }
+}
+
+fn is_string_type(field: &FieldMetadata) -> bool {
+    matches!(
+        field.ty.unwrapped().name(),
+        Some("String" | "str" | "PathBuf" | "char")
+    )
+}
+
+fn display_path(path: &[String]) -> String {
+    if path.is_empty() {
+        "<root>".to_string()
+    } else {
+        path.join(".")
+    }
+}
+
+fn kind_name(kind: ValueKind) -> &'static str {
+    match kind {
+        ValueKind::Bool => "a boolean",
+        ValueKind::Integer => "an integer",
+        ValueKind::Float => "a number",
+        ValueKind::String => "a string",
+        ValueKind::Array => "an array",
+        ValueKind::Map => "a table",
+    }
+}
+
+fn value_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(n) if n.is_f64() => "a float",
+        Value::Number(_) => "an integer",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "a table",
+    }
+}
+
+/// closest candidate within a third of the key's length in edit distance
+fn did_you_mean<'a>(key: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (key.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (edit_distance(key, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance, where swapping two adjacent chars is also a single edit as that is
+/// the most common typo, i.e. `prot` for `port`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before_prev: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != cb);
+            let mut distance = substitute.min(prev[j + 1] + 1).min(cur[j] + 1);
+            if i > 0 && j > 0 && *ca == b[j - 1] && a[i - 1] == *cb {
+                distance = distance.min(before_prev[j - 1] + 1);
+            }
+            cur.push(distance);
+        }
+        before_prev = std::mem::replace(&mut prev, cur);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use opt2doc_core::{CompsiteKind, TypeDescriptor, VariantMetadata};
+    use serde_json::json;
+
+    use super::*;
+
+    fn field(ty: &str, default: Option<&str>) -> FieldMetadata {
+        FieldMetadata {
+            ty: TypeDescriptor::named(ty),
+            default: default.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn item(name: &str, fields: &[(&str, FieldMetadata)]) -> CompsiteMetadata {
+        CompsiteMetadata {
+            name: name.to_string(),
+            fields: fields
+                .iter()
+                .map(|(key, field)| (key.to_string(), field.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn unit_enum(name: &str, variants: &[&str]) -> CompsiteMetadata {
+        CompsiteMetadata {
+            name: name.to_string(),
+            kind: CompsiteKind::Enum,
+            variants: variants
+                .iter()
+                .map(|variant| VariantMetadata {
+                    name: variant.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// `(path, message)` of each diagnostic found checking `config` against `Config`
+    fn check(config: Value) -> Vec<(String, String)> {
+        let items = crate::index_items(vec![
+            item(
+                "Config",
+                &[
+                    ("name", field("String", None)),
+                    ("port", field("u16", Some("80"))),
+                    ("mode", field("Mode", Some("Fast"))),
+                    ("db", field("Db", None)),
+                ],
+            ),
+            item("Db", &[("url", field("String", Some("\"sqlite://\"")))]),
+            unit_enum("Mode", &["Fast", "Slow"]),
+        ]);
+        let root = items.values().find(|item| item.name == "Config").unwrap();
+        check_config(root, &items, &config)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.path, diagnostic.message))
+            .collect()
+    }
+
+    fn diagnostic(path: &str, message: &str) -> (String, String) {
+        (path.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn valid_config_has_no_diagnostics() {
+        let config = json!({ "name": "app", "port": 8080, "mode": "Slow", "db": {} });
+        assert_eq!(check(config), vec![]);
+    }
+
+    #[test]
+    fn unknown_key_suggests_the_closest_key() {
+        let config = json!({ "name": "app", "prot": 8080, "db": { "ulr": "x" } });
+        assert_eq!(
+            check(config),
+            vec![
+                diagnostic("db.ulr", "unknown key, did you mean `url`?"),
+                diagnostic("prot", "unknown key, did you mean `port`?"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_key_far_from_all_keys_has_no_suggestion() {
+        let config = json!({ "name": "app", "db": {}, "listen_address": "::" });
+        assert_eq!(
+            check(config),
+            vec![diagnostic("listen_address", "unknown key")]
+        );
+    }
+
+    #[test]
+    fn missing_required_key() {
+        assert_eq!(
+            check(json!({ "db": {} })),
+            vec![diagnostic("name", "missing required key")]
+        );
+    }
+
+    #[test]
+    fn wrong_type_names_the_expected_type() {
+        let config = json!({ "name": 1, "port": "eighty", "db": { "url": true } });
+        assert_eq!(
+            check(config),
+            vec![
+                diagnostic("db.url", "expected a string (`String`), found a boolean"),
+                diagnostic("name", "expected a string (`String`), found an integer"),
+                diagnostic("port", "expected an integer (`u16`), found a string"),
+            ]
+        );
+    }
+
+    #[test]
+    fn table_given_a_value() {
+        assert_eq!(
+            check(json!({ "name": "app", "db": "sqlite://" })),
+            vec![diagnostic("db", "expected a table, found a string")]
+        );
+    }
+
+    #[test]
+    fn unknown_variant_suggests_the_closest_variant() {
+        let config = json!({ "name": "app", "mode": "Slwo", "db": {} });
+        assert_eq!(
+            check(config),
+            vec![diagnostic(
+                "mode",
+                "unknown variant `Slwo`, did you mean `Slow`?"
+            )]
+        );
+    }
+
+    #[test]
+    fn edit_distance_counts_insertions_deletions_substitutions_and_swaps() {
+        assert_eq!(edit_distance("port", "port"), 0);
+        assert_eq!(edit_distance("port", "ports"), 1);
+        assert_eq!(edit_distance("port", "prt"), 1);
+        assert_eq!(edit_distance("port", "part"), 1);
+        assert_eq!(edit_distance("prot", "port"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn did_you_mean_picks_the_closest_within_a_third_of_the_key() {
+        let candidates = ["timeout", "timeouts", "retries"];
+        assert_eq!(
+            did_you_mean("timout", candidates.into_iter()),
+            Some("timeout")
+        );
+        assert_eq!(
+            did_you_mean("retrys", candidates.into_iter()),
+            Some("retries")
+        );
+        assert_eq!(did_you_mean("workers", candidates.into_iter()), None);
+        // short keys still allow one edit
+        assert_eq!(did_you_mean("ab", ["ac"].into_iter()), Some("ac"));
+    }
+}
@@ -0,0 +1,416 @@
+//! Compare the documented keys with a baseline snapshot checked into the repo, so breaking
+//! config changes can't land without a major version bump.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use itertools::Itertools;
+use opt2doc_parse::parse_type_str;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    args::Args,
+    collect_items,
+    config::ProjectConfig,
+    diff::{collect_keys, compare, parent_of, Change},
+    find_root_items, index_items,
+    render::MAP_KEY_PLACEHOLDER,
+    resolve::display_name,
+    CompsiteMetadata, TypeDescriptor,
+};
+
+/// Contents of the baseline file, the metadata of the last release
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    /// version of every package with documented types, when the snapshot was taken
+    pub versions: BTreeMap<String, String>,
+    pub items: Vec<CompsiteMetadata>,
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            format!(
+                "Failed to read baseline {}: {}, create it with `--update`",
+                path.display(),
+                e
+            )
+        })?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse baseline {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self).unwrap();
+        fs::write(path, content + "\n")
+            .map_err(|e| format!("Failed to write baseline {}: {}", path.display(), e))
+    }
+}
+
+/// A change, and whether configs written for the baseline may stop working because of it
+pub(crate) struct Classified {
+    pub breaking: bool,
+    /// package of the root the change is in, whose version has to show a breaking change
+    pub package: Option<String>,
+    /// `Root.key: what changed`
+    pub description: String,
+}
+
+/// every change from `baseline` to `items`, breaking ones first
+pub(crate) fn classify_changes(
+    baseline: Vec<CompsiteMetadata>,
+    items: Vec<CompsiteMetadata>,
+    roots: &Option<Vec<String>>,
+) -> Vec<Classified> {
+    // a removed root is looked up in the baseline
+    let mut packages = root_packages(baseline.clone(), roots);
+    packages.extend(root_packages(items.clone(), roots));
+    let old = collect_keys(baseline, roots);
+    let new = collect_keys(items, roots);
+    let mut ret = Vec::new();
+    for (root, old_keys) in &old {
+        let package = packages.get(root).cloned().flatten();
+        let Some(new_keys) = new.get(root) else {
+            ret.push(Classified {
+                breaking: true,
+                package,
+                description: format!("`{}`: root removed", root),
+            });
+            continue;
+        };
+        let changes = compare(old_keys, new_keys);
+        let added: Vec<&str> = changes
+            .iter()
+            .filter_map(|change| match change {
+                Change::Added { key, .. } => Some(key.as_str()),
+                _ => None,
+            })
+            .collect();
+        for change in &changes {
+            let (breaking, key, what) = classify(change, &added);
+            ret.push(Classified {
+                breaking,
+                package: package.clone(),
+                description: format!("`{}.{}`: {}", root, key, what),
+            });
+        }
+    }
+    for root in new.keys().filter(|root| !old.contains_key(*root)) {
+        ret.push(Classified {
+            breaking: false,
+            package: packages.get(root).cloned().flatten(),
+            description: format!("`{}`: root added", root),
+        });
+    }
+    ret.sort_by_key(|change| !change.breaking);
+    ret
+}
+
+/// package of every root, keyed the way [`collect_keys`] keys them
+fn root_packages(
+    items: Vec<CompsiteMetadata>,
+    roots: &Option<Vec<String>>,
+) -> BTreeMap<String, Option<String>> {
+    let items = index_items(items);
+    find_root_items(&items, roots)
+        .into_iter()
+        .map(|root| (display_name(root, &items), root.package.clone()))
+        .collect()
+}
+
+/// whether `change` is breaking, the key it is about and what changed, `added` are all keys
+/// added along with it
+fn classify<'a>(change: &'a Change, added: &[&str]) -> (bool, &'a str, String) {
+    match change {
+        Change::Removed { key, .. } => (true, key, "removed".to_string()),
+        Change::Renamed { from, to } => (true, from, format!("renamed to `{}`", to)),
+        // documenting a default where there was none leaves existing configs as they are
+        Change::DefaultChanged { key, from, to } => (
+            from.is_some(),
+            key,
+            format!(
+                "default changed from {} to {}",
+                from.as_deref().unwrap_or("none"),
+                to.as_deref().unwrap_or("none")
+            ),
+        ),
+        Change::TypeChanged { key, from, to } => {
+            let widened = is_widening(&parse_type_str(from), &parse_type_str(to));
+            let what = if widened { "widened" } else { "changed" };
+            (
+                !widened,
+                key,
+                format!("type {} from `{}` to `{}`", what, from, to),
+            )
+        }
+        // a required key in a new table is only breaking if the table is required as well,
+        // which is reported on its own
+        Change::Added { key, info } if info.required && !added.contains(&table_of(key)) => {
+            (true, key, "required key added".to_string())
+        }
+        Change::Added { key, .. } => (false, key, "key added".to_string()),
+        Change::RequiredChanged { key, required } => (
+            *required,
+            key,
+            format!("now {}", if *required { "required" } else { "optional" }),
+        ),
+        Change::Deprecated { key, .. } => (false, key, "deprecated".to_string()),
+        Change::DocChanged { key } => (false, key, "doc changed".to_string()),
+    }
+}
+
+/// key of the table `key` is in, i.e. `backends` for `backends[].host`
+fn table_of(key: &str) -> &str {
+    let parent = parent_of(key);
+    parent
+        .strip_suffix("[]")
+        .or_else(|| parent.strip_suffix(&format!(".{}", MAP_KEY_PLACEHOLDER)))
+        .unwrap_or(parent)
+}
+
+/// every value of type `from` is also a valid `to`, i.e. `u16` to `u32` or `T` to `Option<T>`
+fn is_widening(from: &TypeDescriptor, to: &TypeDescriptor) -> bool {
+    if to.name() == Some("Option") && to.args().first() == Some(from) {
+        return true;
+    }
+    // kind and size of primitive numbers, `usize` is taken as 64 bits
+    let number = |ty: &TypeDescriptor| -> Option<(char, u32)> {
+        let name = ty.name()?;
+        let kind = name.chars().next()?;
+        let bits = match &name[kind.len_utf8()..] {
+            "size" => 64,
+            bits => bits.parse().ok()?,
+        };
+        matches!(kind, 'u' | 'i' | 'f').then_some((kind, bits))
+    };
+    let Some(((from_kind, from_bits), (to_kind, to_bits))) = number(from).zip(number(to)) else {
+        return false;
+    };
+    // unsigned fits in a wider unsigned or signed integer, the others only in their own kind
+    to_bits > from_bits && (from_kind == to_kind || (from_kind, to_kind) == ('u', 'i'))
+}
+
+/// version of every package in the workspace `repo` is in, as cargo sees it, so versions
+/// inherited from the workspace are resolved
+pub(crate) fn package_versions(repo: &Path) -> Result<BTreeMap<String, String>, String> {
+    let output = std::process::Command::new("cargo")
+        .args(["metadata", "--no-deps", "--format-version", "1"])
+        .current_dir(repo)
+        .output()
+        .map_err(|e| format!("Failed to run cargo metadata: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "`cargo metadata` failed in {}: {}",
+            repo.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse the output of cargo metadata: {}", e))?;
+    let packages = metadata["packages"].as_array().cloned().unwrap_or_default();
+    Ok(packages
+        .iter()
+        .filter_map(|package| {
+            let name = package["name"].as_str()?;
+            let version = package["version"].as_str()?;
+            Some((name.to_string(), version.to_string()))
+        })
+        .collect())
+}
+
+/// `to` is not semver compatible with `from` and newer, i.e. `1.4.0` to `2.0.0` or `0.3.1` to
+/// `0.4.0`, following cargo's rule that the leftmost non-zero component is the major one
+pub(crate) fn is_major_bump(from: &str, to: &str) -> Result<bool, String> {
+    let parse = |version: &str| -> Result<[u64; 3], String> {
+        // pre-release and build metadata don't matter here
+        let core = version.split(['-', '+']).next().unwrap_or_default();
+        let parts: Vec<u64> = core
+            .split('.')
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("`{}` is not a semver version", version))?;
+        match parts.as_slice() {
+            [major, minor, patch] => Ok([*major, *minor, *patch]),
+            _ => Err(format!("`{}` is not a semver version", version)),
+        }
+    };
+    let (from, to) = (parse(from)?, parse(to)?);
+    let significant = from.iter().position(|part| *part != 0).unwrap_or(2);
+    Ok(to[..=significant] > from[..=significant])
+}
+
+/// compare the collected metadata with the baseline at `path`, or overwrite it with `update`,
+/// returns false if there are breaking changes in a package without a major version bump
+pub(crate) fn run_compat(
+    args: &Args,
+    project: &ProjectConfig,
+    path: &Path,
+    update: bool,
+) -> Result<bool, String> {
+    let items = collect_items(args, project);
+    let mut versions = package_versions(&args.repo)?;
+    if update {
+        // only the packages the snapshot is about
+        versions.retain(|package, _| {
+            items
+                .iter()
+                .any(|item| item.package.as_ref() == Some(package))
+        });
+        Baseline { versions, items }.save(path)?;
+        eprintln!("Wrote baseline {}", path.display());
+        return Ok(true);
+    }
+
+    let baseline = Baseline::load(path)?;
+    let changes = classify_changes(baseline.items, items, &args.root);
+    for change in &changes {
+        let label = if change.breaking {
+            "\u{001b}[1;31mbreaking\u{001b}[0m"
+        } else {
+            "\u{001b}[1;32mcompatible\u{001b}[0m"
+        };
+        eprintln!("{}: {}", label, change.description);
+    }
+    let breaking = changes.iter().filter(|change| change.breaking).count();
+    eprintln!(
+        "{} breaking and {} compatible change(s) since {}",
+        breaking,
+        changes.len() - breaking,
+        path.display()
+    );
+
+    let mut ok = true;
+    let packages: BTreeMap<Option<&String>, usize> = changes
+        .iter()
+        .filter(|change| change.breaking)
+        .map(|change| change.package.as_ref())
+        .counts()
+        .into_iter()
+        .collect();
+    for (package, count) in packages {
+        let Some(package) = package else {
+            eprintln!(
+                "\u{001b}[1;31merror\u{001b}[0m: {} breaking config change(s) in types of an \
+                 unknown package, collect the metadata with this version of opt2doc",
+                count
+            );
+            ok = false;
+            continue;
+        };
+        let (Some(from), Some(to)) = (baseline.versions.get(package), versions.get(package)) else {
+            eprintln!(
+                "\u{001b}[1;31merror\u{001b}[0m: {} breaking config change(s) in `{}`, which \
+                 has no version in both the baseline and the workspace",
+                count, package
+            );
+            ok = false;
+            continue;
+        };
+        if is_major_bump(from, to)? {
+            eprintln!(
+                "{} breaking change(s) in `{}` allowed by the major version bump from {} to {}, \
+                 run with `--update` after the release",
+                count, package, from, to
+            );
+        } else {
+            eprintln!(
+                "\u{001b}[1;31merror\u{001b}[0m: {} breaking config change(s) in `{}` need a \
+                 major version bump, the version is still {}",
+                count, package, to
+            );
+            ok = false;
+        }
+    }
+    Ok(ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn widens(from: &str, to: &str) -> bool {
+        is_widening(&parse_type_str(from), &parse_type_str(to))
+    }
+
+    #[test]
+    fn wider_numbers() {
+        assert!(widens("u16", "u32"));
+        assert!(widens("u16", "i32"));
+        assert!(widens("i32", "i64"));
+        assert!(widens("f32", "f64"));
+        assert!(widens("u32", "usize"));
+    }
+
+    #[test]
+    fn narrower_or_other_kind_numbers() {
+        assert!(!widens("u32", "u16"));
+        assert!(!widens("u16", "i16"));
+        assert!(!widens("i16", "u32"));
+        assert!(!widens("i32", "f64"));
+        assert!(!widens("u64", "u64"));
+        assert!(!widens("usize", "u64"));
+    }
+
+    #[test]
+    fn option_of_same_type() {
+        assert!(widens("String", "Option<String>"));
+        assert!(widens("Vec<u8>", "Option<Vec<u8>>"));
+        assert!(!widens("Option<String>", "String"));
+        assert!(!widens("u16", "Option<u32>"));
+        assert!(!widens("String", "Vec<String>"));
+    }
+
+    #[test]
+    fn major_bump_of_stable_versions() {
+        assert!(is_major_bump("1.4.2", "2.0.0").unwrap());
+        assert!(!is_major_bump("1.4.2", "1.5.0").unwrap());
+        assert!(!is_major_bump("1.4.2", "1.4.3").unwrap());
+        assert!(!is_major_bump("2.0.0", "1.9.0").unwrap());
+    }
+
+    #[test]
+    fn minor_and_patch_count_as_major_before_1_0() {
+        assert!(is_major_bump("0.3.1", "0.4.0").unwrap());
+        assert!(!is_major_bump("0.3.1", "0.3.2").unwrap());
+        assert!(is_major_bump("0.0.3", "0.0.4").unwrap());
+        assert!(is_major_bump("0.9.0", "1.0.0").unwrap());
+    }
+
+    #[test]
+    fn pre_release_and_build_metadata_are_ignored() {
+        assert!(is_major_bump("1.2.0", "2.0.0-rc.1").unwrap());
+        assert!(!is_major_bump("1.2.0-beta", "1.2.0").unwrap());
+        assert!(!is_major_bump("1.2.0+build.5", "1.3.0+build.6").unwrap());
+    }
+
+    #[test]
+    fn invalid_versions_are_errors() {
+        assert!(is_major_bump("1.2", "2.0.0").is_err());
+        assert!(is_major_bump("1.2.0", "next").is_err());
+    }
+
+    fn breaking(change: Change) -> bool {
+        classify(&change, &[]).0
+    }
+
+    #[test]
+    fn only_changing_an_existing_default_is_breaking() {
+        let default_changed = |from: Option<&str>, to: Option<&str>| Change::DefaultChanged {
+            key: "port".to_string(),
+            from: from.map(str::to_string),
+            to: to.map(str::to_string),
+        };
+        assert!(breaking(default_changed(Some("80"), Some("8080"))));
+        assert!(breaking(default_changed(Some("80"), None)));
+        assert!(!breaking(default_changed(None, Some("80"))));
+    }
+
+    #[test]
+    fn only_becoming_required_is_breaking() {
+        let required_changed = |required| Change::RequiredChanged {
+            key: "port".to_string(),
+            required,
+        };
+        assert!(breaking(required_changed(true)));
+        assert!(!breaking(required_changed(false)));
+    }
+}
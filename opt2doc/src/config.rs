@@ -0,0 +1,148 @@
+//! Project-wide settings read from `opt2doc.toml`, so every invocation shares one checked-in
+//! source of truth instead of repeating flags.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use opt2doc_parse::parse_type_str;
+use serde::Deserialize;
+
+use crate::{
+    args::{RenderFormat, Transport},
+    CompsiteMetadata, KeyCase, KeyStyle, TypeDescriptor,
+};
+
+/// looked up at the workspace root when `--config` is not given
+pub const CONFIG_FILE_NAME: &str = "opt2doc.toml";
+
+/// Contents of `opt2doc.toml`, every key mirrors the command line flag of the same name, which
+/// wins when given. Relative paths are relative to the file itself.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    pub repo: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub render: Option<Vec<RenderFormat>>,
+    pub root: Option<Vec<String>>,
+    pub transport: Option<Transport>,
+    pub scan: Option<bool>,
+    pub from_metadata: Option<PathBuf>,
+    pub check: Option<bool>,
+    pub key_style: Option<KeyStyle>,
+    pub delimiter: Option<String>,
+    pub key_prefix: Option<String>,
+    pub env_prefix: Option<String>,
+    pub env_case: Option<KeyCase>,
+    pub package: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    /// types to document differently than written, i.e. `"chrono::DateTime<Utc>" = "String"`,
+    /// a key without `::` or generics matches every type of that name
+    pub types: BTreeMap<String, String>,
+    /// settings of a single format, i.e. `[format.json-schema]`
+    pub format: BTreeMap<RenderFormat, FormatOptions>,
+}
+
+/// Overrides of the top level settings for one render format
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FormatOptions {
+    pub output: Option<PathBuf>,
+    pub root: Option<Vec<String>>,
+}
+
+impl ProjectConfig {
+    /// read `path`, resolving the relative paths in it against its directory
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut config: Self = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+        let base = path.parent().unwrap_or(Path::new("."));
+        let outputs = config
+            .format
+            .values_mut()
+            .map(|options| &mut options.output);
+        for path in [
+            &mut config.repo,
+            &mut config.output,
+            &mut config.from_metadata,
+        ]
+        .into_iter()
+        .chain(outputs)
+        .flatten()
+        {
+            *path = base.join(&*path);
+        }
+        Ok(config)
+    }
+
+    /// `opt2doc.toml` at the root of the workspace `repo` is in, if there is one
+    pub fn discover(repo: &Path) -> Option<PathBuf> {
+        let repo = repo.canonicalize().ok()?;
+        let root = repo
+            .ancestors()
+            .find(|dir| is_workspace_root(dir))
+            .unwrap_or(&repo);
+        let path = root.join(CONFIG_FILE_NAME);
+        path.is_file().then_some(path)
+    }
+
+    /// apply [`Self::types`] to the fields of every item
+    pub fn override_types(&self, items: &mut [CompsiteMetadata]) {
+        if self.types.is_empty() {
+            return;
+        }
+        let overrides: Vec<(&str, TypeDescriptor)> = self
+            .types
+            .iter()
+            .map(|(from, to)| (from.as_str(), parse_type_str(to)))
+            .collect();
+        let fields = items.iter_mut().flat_map(|item| {
+            item.fields.iter_mut().chain(
+                item.variants
+                    .iter_mut()
+                    .flat_map(|variant| variant.fields.iter_mut()),
+            )
+        });
+        for (_, field) in fields {
+            override_type(&mut field.ty, &overrides);
+        }
+    }
+}
+
+/// replace `ty`, or the types nested in it, i.e. `Option<DateTime<Utc>>` becomes
+/// `Option<String>`
+fn override_type(ty: &mut TypeDescriptor, overrides: &[(&str, TypeDescriptor)]) {
+    let written = ty.to_string();
+    let name = ty.name().map(str::to_string);
+    if let Some((_, to)) = overrides
+        .iter()
+        .find(|(from, _)| *from == written || Some(*from) == name.as_deref())
+    {
+        *ty = to.clone();
+        return;
+    }
+    match ty {
+        TypeDescriptor::Path { args, .. } => args
+            .iter_mut()
+            .for_each(|arg| override_type(arg, overrides)),
+        TypeDescriptor::Tuple { elems } => elems
+            .iter_mut()
+            .for_each(|elem| override_type(elem, overrides)),
+        TypeDescriptor::Array { elem, .. }
+        | TypeDescriptor::Slice { elem }
+        | TypeDescriptor::Reference { elem, .. } => override_type(elem, overrides),
+        TypeDescriptor::Other { .. } => {}
+    }
+}
+
+fn is_workspace_root(dir: &Path) -> bool {
+    fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| toml::from_str::<toml::Table>(&manifest).ok())
+        .is_some_and(|manifest| manifest.contains_key("workspace"))
+}
@@ -0,0 +1,582 @@
+//! Compare the keys documented at two git revisions, and write what changed as release notes.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use itertools::Itertools;
+
+use crate::{
+    args::Args,
+    collect_items,
+    config::ProjectConfig,
+    find_root_items, index_items,
+    render::{build_tree, ConfigNode, NodeKind},
+    resolve::display_name,
+    CompsiteMetadata,
+};
+
+/// What is documented about a single key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct KeyInfo {
+    /// the rust type as written
+    pub ty: String,
+    pub default: Option<String>,
+    pub deprecated: Option<String>,
+    pub required: bool,
+    pub doc: String,
+    /// a nested table, or a list or map of them, whose keys are listed separately
+    pub table: bool,
+}
+
+/// keys of every root by their dotted path, i.e. `backends[].host`, roots are keyed by name
+pub(crate) type RootKeys = BTreeMap<String, BTreeMap<String, KeyInfo>>;
+
+/// expand every root picked by `roots` into its keys
+pub(crate) fn collect_keys(items: Vec<CompsiteMetadata>, roots: &Option<Vec<String>>) -> RootKeys {
+    let items = index_items(items);
+    find_root_items(&items, roots)
+        .into_iter()
+        .map(|root| {
+            let mut keys = BTreeMap::new();
+            flatten(&build_tree(root, &items), &[], &mut keys);
+            (display_name(root, &items), keys)
+        })
+        .collect()
+}
+
+fn flatten(nodes: &[ConfigNode], path: &[String], out: &mut BTreeMap<String, KeyInfo>) {
+    for node in nodes {
+        let mut key = path.to_vec();
+        key.push(node.key.clone());
+        out.insert(
+            key.join("."),
+            KeyInfo {
+                ty: node.field.ty.to_string(),
+                default: node.field.default.clone(),
+                deprecated: node.field.deprecation(),
+                required: node.is_required(),
+                doc: node.doc().trim().to_string(),
+                table: !matches!(node.kind, NodeKind::Value { .. }),
+            },
+        );
+        if let NodeKind::Table { children, .. } = &node.kind {
+            flatten(children, &node.inner_path(path), out);
+        }
+    }
+}
+
+/// One difference between the keys of a root at two revisions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Change {
+    Added {
+        key: String,
+        info: KeyInfo,
+    },
+    Removed {
+        key: String,
+        info: KeyInfo,
+    },
+    /// a key replaced by another one in the same table, with the same type and default
+    Renamed {
+        from: String,
+        to: String,
+    },
+    DefaultChanged {
+        key: String,
+        from: Option<String>,
+        to: Option<String>,
+    },
+    TypeChanged {
+        key: String,
+        from: String,
+        to: String,
+    },
+    /// the key has to be set now, or can be left out now if `required` is false
+    RequiredChanged {
+        key: String,
+        required: bool,
+    },
+    Deprecated {
+        key: String,
+        note: String,
+    },
+    /// only the doc comment changed, left out of release notes
+    DocChanged {
+        key: String,
+    },
+}
+
+/// changes from `old` to `new`, keys of a renamed table are renamed along with it and not
+/// listed on their own
+pub(crate) fn compare(
+    old: &BTreeMap<String, KeyInfo>,
+    new: &BTreeMap<String, KeyInfo>,
+) -> Vec<Change> {
+    let mut removed: Vec<&String> = old.keys().filter(|key| !new.contains_key(*key)).collect();
+    let mut added: Vec<&String> = new.keys().filter(|key| !old.contains_key(*key)).collect();
+    // pairs of keys that are the same, under their old and new path
+    let mut same: Vec<(&String, &String)> = old
+        .keys()
+        .filter(|key| new.contains_key(*key))
+        .map(|key| (key, key))
+        .collect();
+
+    // shallowest first, so keys in a renamed table can follow it
+    removed.sort_by_key(|key| key.matches('.').count());
+    let mut renames: Vec<(&String, &String)> = Vec::new();
+    let mut changes = Vec::new();
+    for from in removed.clone() {
+        let moved = renames.iter().find_map(|(old_table, new_table)| {
+            let rest = from.strip_prefix(old_table.as_str())?;
+            (rest.starts_with('.') || rest.starts_with('['))
+                .then(|| format!("{}{}", new_table, rest))
+        });
+        let to = match &moved {
+            Some(moved) => added.iter().copied().find(|key| *key == moved),
+            None => {
+                let info = &old[from];
+                let parent = renamed_parent(parent_of(from), &renames);
+                let mut to = added
+                    .iter()
+                    .copied()
+                    .filter(|key| parent_of(key) == parent && same_shape(info, &new[*key]));
+                // only a rename if nothing else in either table looks the same
+                let look_alike = removed
+                    .iter()
+                    .filter(|key| {
+                        parent_of(key) == parent_of(from) && same_shape(info, &old[key.as_str()])
+                    })
+                    .count();
+                match (to.next(), to.next()) {
+                    (Some(to), None) if look_alike == 1 => Some(to),
+                    _ => None,
+                }
+            }
+        };
+        if let Some(to) = to {
+            if moved.is_none() {
+                changes.push(Change::Renamed {
+                    from: from.clone(),
+                    to: to.clone(),
+                });
+            }
+            renames.push((from, to));
+            same.push((from, to));
+            removed.retain(|key| *key != from);
+            added.retain(|key| *key != to);
+        }
+    }
+
+    for (from, to) in same.into_iter().sorted_by_key(|(_, to)| *to) {
+        let (before, after) = (&old[from], &new[to]);
+        if before.default != after.default {
+            changes.push(Change::DefaultChanged {
+                key: to.clone(),
+                from: before.default.clone(),
+                to: after.default.clone(),
+            });
+        }
+        // the type of a table is its struct, the keys inside tell what changed
+        if before.ty != after.ty && !(before.table && after.table) {
+            changes.push(Change::TypeChanged {
+                key: to.clone(),
+                from: before.ty.clone(),
+                to: after.ty.clone(),
+            });
+        }
+        if before.required != after.required {
+            changes.push(Change::RequiredChanged {
+                key: to.clone(),
+                required: after.required,
+            });
+        }
+        if let (None, Some(note)) = (&before.deprecated, &after.deprecated) {
+            changes.push(Change::Deprecated {
+                key: to.clone(),
+                note: note.clone(),
+            });
+        }
+        if before.doc != after.doc {
+            changes.push(Change::DocChanged { key: to.clone() });
+        }
+    }
+    changes.extend(added.into_iter().sorted().map(|key| Change::Added {
+        key: key.clone(),
+        info: new[key].clone(),
+    }));
+    changes.extend(removed.into_iter().sorted().map(|key| Change::Removed {
+        key: key.clone(),
+        info: old[key].clone(),
+    }));
+    changes
+}
+
+/// the table a key is in, i.e. `backends[]` for `backends[].host`
+pub(crate) fn parent_of(key: &str) -> &str {
+    key.rsplit_once('.').map_or("", |(parent, _)| parent)
+}
+
+/// `parent` under the name its table was renamed to, if it was
+fn renamed_parent(parent: &str, renames: &[(&String, &String)]) -> String {
+    renames
+        .iter()
+        .find_map(|(from, to)| {
+            let rest = parent.strip_prefix(from.as_str())?;
+            (rest.is_empty() || rest.starts_with('.') || rest.starts_with('['))
+                .then(|| format!("{}{}", to, rest))
+        })
+        .unwrap_or_else(|| parent.to_string())
+}
+
+fn same_shape(a: &KeyInfo, b: &KeyInfo) -> bool {
+    a.ty == b.ty && a.default == b.default && a.table == b.table
+}
+
+/// release notes for the changes from `old` to `new`, `from` and `to` name the revisions
+pub(crate) fn changes_to_markdown(from: &str, to: &str, old: &RootKeys, new: &RootKeys) -> String {
+    let mut output = format!("# Configuration changes from `{}` to `{}`\n", from, to);
+    let mut any = false;
+    for root in old.keys().chain(new.keys()).unique().sorted() {
+        let section = match (old.get(root), new.get(root)) {
+            (None, Some(keys)) => format!("New, with {} key(s).\n", keys.len()),
+            (Some(_), None) => "Removed.\n".to_string(),
+            (Some(old), Some(new)) => changes_section(&compare(old, new)),
+            (None, None) => unreachable!(),
+        };
+        if !section.is_empty() {
+            any = true;
+            output.push_str(&format!("\n## `{}`\n\n{}", root, section));
+        }
+    }
+    if !any {
+        output.push_str("\nNo configuration changes.\n");
+    }
+    output
+}
+
+fn changes_section(changes: &[Change]) -> String {
+    let mut groups: BTreeMap<usize, (&str, Vec<String>)> = BTreeMap::new();
+    for change in changes {
+        let (order, title, line) = match change {
+            Change::Added { key, info } => {
+                let mut line = format!("`{}` (`{}`", key, info.ty);
+                match &info.default {
+                    Some(default) => line.push_str(&format!(", default `{}`)", default)),
+                    None if info.required => line.push_str(", required)"),
+                    None => line.push(')'),
+                }
+                if let Some(doc) = info.doc.lines().next() {
+                    line.push_str(&format!(": {}", doc));
+                }
+                (0, "Added", line)
+            }
+            Change::Removed { key, .. } => (1, "Removed", format!("`{}`", key)),
+            Change::Renamed { from, to } => (2, "Renamed", format!("`{}` to `{}`", from, to)),
+            Change::DefaultChanged { key, from, to } => (
+                3,
+                "Changed defaults",
+                format!("`{}`: {} to {}", key, code_or_none(from), code_or_none(to)),
+            ),
+            Change::TypeChanged { key, from, to } => (
+                4,
+                "Changed types",
+                format!("`{}`: `{}` to `{}`", key, from, to),
+            ),
+            Change::RequiredChanged { key, required } => (
+                5,
+                "Changed requirements",
+                format!(
+                    "`{}`: now {}",
+                    key,
+                    if *required { "required" } else { "optional" }
+                ),
+            ),
+            Change::Deprecated { key, note } => (6, "Deprecated", format!("`{}`: {}", key, note)),
+            Change::DocChanged { .. } => continue,
+        };
+        groups
+            .entry(order)
+            .or_insert_with(|| (title, Vec::new()))
+            .1
+            .push(line);
+    }
+    groups
+        .into_values()
+        .map(|(title, lines)| {
+            let lines: String = lines.iter().map(|line| format!("- {}\n", line)).collect();
+            format!("### {}\n\n{}", title, lines)
+        })
+        .join("\n")
+}
+
+fn code_or_none(value: &Option<String>) -> String {
+    value
+        .as_ref()
+        .map_or("no default".to_string(), |value| format!("`{}`", value))
+}
+
+/// collect metadata at both revisions and write the changes between them as markdown, to
+/// `out` or stdout
+pub(crate) fn run_diff(
+    args: &Args,
+    project: &ProjectConfig,
+    from: &str,
+    to: &str,
+    out: Option<&Path>,
+) -> Result<(), String> {
+    let toplevel = PathBuf::from(git(&args.repo, &["rev-parse", "--show-toplevel"])?);
+    let repo = args
+        .repo
+        .canonicalize()
+        .map_err(|e| format!("Failed to open {}: {}", args.repo.display(), e))?;
+    // the same directory in the checkouts
+    let subdir = repo.strip_prefix(&toplevel).unwrap_or(Path::new(""));
+
+    let mut keys = Vec::new();
+    for rev in [from, to] {
+        let worktree = Worktree::add(&toplevel, rev)?;
+        let mut args = args.clone();
+        args.repo = worktree.dir.join(subdir);
+        // a metadata file is of one revision only, the sources of both are read instead
+        args.from_metadata = None;
+        keys.push(collect_keys(collect_items(&args, project), &args.root));
+    }
+    let markdown = changes_to_markdown(from, to, &keys[0], &keys[1]);
+    match out {
+        Some(out) => std::fs::write(out, markdown)
+            .map_err(|e| format!("Failed to write {}: {}", out.display(), e)),
+        None => {
+            print!("{}", markdown);
+            Ok(())
+        }
+    }
+}
+
+/// A detached checkout of a revision in a temporary directory, removed again when dropped
+struct Worktree {
+    toplevel: PathBuf,
+    dir: PathBuf,
+}
+
+impl Worktree {
+    fn add(toplevel: &Path, rev: &str) -> Result<Self, String> {
+        let dir = std::env::temp_dir().join(format!(
+            "opt2doc-{}-{}",
+            std::process::id(),
+            rev.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        ));
+        let path = dir.to_string_lossy();
+        git(
+            toplevel,
+            &["worktree", "add", "--detach", "--quiet", &path, rev],
+        )?;
+        Ok(Self {
+            toplevel: toplevel.to_path_buf(),
+            dir,
+        })
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let path = self.dir.to_string_lossy();
+        if let Err(err) = git(&self.toplevel, &["worktree", "remove", "--force", &path]) {
+            eprintln!("{}", err);
+        }
+    }
+}
+
+/// run git in `dir`, returns its trimmed stdout
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(ty: &str, default: Option<&str>) -> KeyInfo {
+        KeyInfo {
+            ty: ty.to_string(),
+            default: default.map(str::to_string),
+            deprecated: None,
+            required: default.is_none(),
+            doc: String::new(),
+            table: false,
+        }
+    }
+
+    fn table(ty: &str) -> KeyInfo {
+        KeyInfo {
+            table: true,
+            required: true,
+            ..value(ty, None)
+        }
+    }
+
+    fn keys(keys: &[(&str, KeyInfo)]) -> BTreeMap<String, KeyInfo> {
+        keys.iter()
+            .map(|(key, info)| (key.to_string(), info.clone()))
+            .collect()
+    }
+
+    fn renamed(from: &str, to: &str) -> Change {
+        Change::Renamed {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    #[test]
+    fn rename_in_same_table() {
+        let old = keys(&[("port", value("u16", Some("80")))]);
+        let new = keys(&[("listen_port", value("u16", Some("80")))]);
+        assert_eq!(compare(&old, &new), vec![renamed("port", "listen_port")]);
+    }
+
+    #[test]
+    fn renamed_table_carries_its_keys() {
+        let old = keys(&[
+            ("tls", table("Tls")),
+            ("tls.enabled", value("bool", Some("false"))),
+            ("backends", table("Vec<Backend>")),
+            ("backends[].host", value("String", None)),
+        ]);
+        let new = keys(&[
+            ("security", table("Tls")),
+            ("security.enabled", value("bool", Some("false"))),
+            ("upstreams", table("Vec<Backend>")),
+            ("upstreams[].host", value("String", None)),
+        ]);
+        let changes = compare(&old, &new);
+        assert_eq!(changes.len(), 2, "{:?}", changes);
+        assert!(changes.contains(&renamed("tls", "security")));
+        assert!(changes.contains(&renamed("backends", "upstreams")));
+    }
+
+    #[test]
+    fn key_of_renamed_table_changes_too() {
+        let old = keys(&[
+            ("tls", table("Tls")),
+            ("tls.enabled", value("bool", Some("false"))),
+        ]);
+        let new = keys(&[
+            ("security", table("Tls")),
+            ("security.enabled", value("bool", Some("true"))),
+        ]);
+        assert_eq!(
+            compare(&old, &new),
+            vec![
+                renamed("tls", "security"),
+                Change::DefaultChanged {
+                    key: "security.enabled".to_string(),
+                    from: Some("false".to_string()),
+                    to: Some("true".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn look_alike_removed_keys_are_not_renamed() {
+        let old = keys(&[
+            ("read_timeout", value("u64", Some("30"))),
+            ("write_timeout", value("u64", Some("30"))),
+        ]);
+        let new = keys(&[("timeout", value("u64", Some("30")))]);
+        let changes = compare(&old, &new);
+        assert!(
+            !changes
+                .iter()
+                .any(|change| matches!(change, Change::Renamed { .. })),
+            "{:?}",
+            changes
+        );
+        assert_eq!(changes.len(), 3);
+    }
+
+    #[test]
+    fn ambiguous_candidates_are_not_renamed() {
+        let old = keys(&[("timeout", value("u64", Some("30")))]);
+        let new = keys(&[
+            ("read_timeout", value("u64", Some("30"))),
+            ("write_timeout", value("u64", Some("30"))),
+        ]);
+        let changes = compare(&old, &new);
+        assert!(
+            !changes
+                .iter()
+                .any(|change| matches!(change, Change::Renamed { .. })),
+            "{:?}",
+            changes
+        );
+        assert_eq!(changes.len(), 3);
+    }
+
+    #[test]
+    fn different_default_or_table_is_not_a_rename() {
+        let old = keys(&[("port", value("u16", Some("80")))]);
+        let new = keys(&[("listen_port", value("u16", Some("8080")))]);
+        assert!(matches!(
+            compare(&old, &new).as_slice(),
+            [Change::Added { .. }, Change::Removed { .. }]
+        ));
+
+        let old = keys(&[("port", value("u16", Some("80")))]);
+        let new = keys(&[("nested.port", value("u16", Some("80")))]);
+        assert!(matches!(
+            compare(&old, &new).as_slice(),
+            [Change::Added { .. }, Change::Removed { .. }]
+        ));
+    }
+
+    #[test]
+    fn table_type_change_is_left_to_its_keys() {
+        let old = keys(&[("tls", table("Tls"))]);
+        let new = keys(&[("tls", table("TlsConfig"))]);
+        assert_eq!(compare(&old, &new), Vec::new());
+
+        let old = keys(&[("port", value("u16", Some("80")))]);
+        let new = keys(&[("port", value("u32", Some("80")))]);
+        assert_eq!(
+            compare(&old, &new),
+            vec![Change::TypeChanged {
+                key: "port".to_string(),
+                from: "u16".to_string(),
+                to: "u32".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn required_change_is_reported() {
+        let optional = KeyInfo {
+            required: false,
+            ..value("String", None)
+        };
+        let old = keys(&[("name", optional.clone())]);
+        let new = keys(&[("name", value("String", None))]);
+        let required = |required| {
+            vec![Change::RequiredChanged {
+                key: "name".to_string(),
+                required,
+            }]
+        };
+        assert_eq!(compare(&old, &new), required(true));
+        assert_eq!(compare(&new, &old), required(false));
+    }
+}
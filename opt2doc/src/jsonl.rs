@@ -0,0 +1,73 @@
+use std::{
+    fs::{read_to_string, remove_file},
+    path::PathBuf,
+};
+
+use opt2doc_core::jsonl::JSONL_ENV_VAR_NAME;
+
+use crate::{transport::MetadataReceiver, CompsiteMetadata};
+
+/// Read back all the types appended by `opt2doc_core::jsonl::JsonlClientState` during one run
+pub struct JsonlServerState {
+    path: PathBuf,
+}
+
+impl JsonlServerState {
+    /// `path` should be unique per run and absolute, since rustdoc runs in each crate's own dir,
+    /// any stale file left there is removed
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let _ = remove_file(&path);
+        Self { path }
+    }
+
+    /// a file in the temp dir unique to this process
+    pub fn per_run_path() -> PathBuf {
+        std::env::temp_dir().join(format!("opt2doc-{}.jsonl", std::process::id()))
+    }
+}
+
+impl MetadataReceiver for JsonlServerState {
+    fn envs(&self) -> Vec<(String, String)> {
+        vec![(
+            JSONL_ENV_VAR_NAME.to_string(),
+            self.path.to_string_lossy().to_string(),
+        )]
+    }
+
+    fn finish(&mut self) -> Vec<CompsiteMetadata> {
+        // nothing documented means nothing appended, so the file may not exist
+        if !self.path.exists() {
+            return Vec::new();
+        }
+        let content = match read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!(
+                    "\u{001b}[1;93mwarning\u{001b}[0m: failed to read {}: {}",
+                    self.path.display(),
+                    e
+                );
+                return Vec::new();
+            }
+        };
+        let mut ret = Vec::new();
+        for (idx, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            // i.e. a rustdoc killed in the middle of a write leaves a partial line
+            match serde_json::from_str(line) {
+                Ok(compsite) => ret.push(compsite),
+                Err(e) => eprintln!(
+                    "\u{001b}[1;93mwarning\u{001b}[0m: skipped line {} of {}: {}",
+                    idx + 1,
+                    self.path.display(),
+                    e
+                ),
+            }
+        }
+        let _ = remove_file(&self.path);
+        ret
+    }
+}
@@ -0,0 +1,186 @@
+//! How the path of a nested key is written, i.e. `inner.cfg` in a config file or
+//! `APP__INNER__CFG` as an environment variable.
+
+use itertools::Itertools;
+use serde::Deserialize;
+
+use crate::render::MAP_KEY_PLACEHOLDER;
+
+/// stands for the position of an element in a list of tables when keys are written as env vars,
+/// i.e. `BACKENDS__<INDEX>__HOST`
+pub const LIST_INDEX_PLACEHOLDER: &str = "<index>";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum, opt2doc_derive::Opt2Doc))]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyStyle {
+    /// `backends[].host`, as written in a config file
+    #[default]
+    Dotted,
+    /// `BACKENDS__<INDEX>__HOST`, upper case as environment variables are usually written
+    Env,
+}
+
+/// Case of keys written as env vars, dotted keys are written as they are
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum, opt2doc_derive::Opt2Doc))]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyCase {
+    /// `APP__DATABASE__URL`
+    #[default]
+    Upper,
+    /// `app__database__url`
+    Lower,
+}
+
+/// Writes a key path given as segments, a list of tables is a segment ending with `[]` and an
+/// entry of a map of tables is a `<name>` segment, i.e. `["backends[]", "host"]`
+#[derive(Debug, Default, Clone)]
+pub struct KeyFormat {
+    pub style: KeyStyle,
+    /// `.` for dotted keys and `__` for env vars if not set
+    pub delimiter: Option<String>,
+    /// put before every key, i.e. the name of the root table or the env var prefix
+    pub prefix: Option<String>,
+    pub case: KeyCase,
+}
+
+impl KeyFormat {
+    pub fn delimiter(&self) -> &str {
+        match (&self.delimiter, self.style) {
+            (Some(delimiter), _) => delimiter,
+            (None, KeyStyle::Dotted) => ".",
+            (None, KeyStyle::Env) => "__",
+        }
+    }
+
+    pub fn key(&self, path: &[String]) -> String {
+        let mut parts = self.prefix.iter().chain(path).map(String::as_str);
+        match self.style {
+            KeyStyle::Dotted => parts.join(self.delimiter()),
+            KeyStyle::Env => parts
+                .flat_map(|part| match part.strip_suffix("[]") {
+                    Some(list) => vec![list, LIST_INDEX_PLACEHOLDER],
+                    None => vec![part],
+                })
+                .map(|part| env_segment(part, self.case))
+                .join(self.delimiter()),
+        }
+    }
+}
+
+/// in `case`, with anything but letters and digits replaced by `_` as only those are safe in
+/// env var names, placeholders are kept readable
+fn env_segment(part: &str, case: KeyCase) -> String {
+    let part = match case {
+        KeyCase::Upper => part.to_ascii_uppercase(),
+        KeyCase::Lower => part.to_ascii_lowercase(),
+    };
+    if part.eq_ignore_ascii_case(MAP_KEY_PLACEHOLDER)
+        || part.eq_ignore_ascii_case(LIST_INDEX_PLACEHOLDER)
+    {
+        return part;
+    }
+    part.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(segments: &[&str]) -> Vec<String> {
+        segments.iter().map(ToString::to_string).collect()
+    }
+
+    fn env(delimiter: Option<&str>, prefix: Option<&str>, case: KeyCase) -> KeyFormat {
+        KeyFormat {
+            style: KeyStyle::Env,
+            delimiter: delimiter.map(str::to_string),
+            prefix: prefix.map(str::to_string),
+            case,
+        }
+    }
+
+    #[test]
+    fn dotted() {
+        let format = KeyFormat::default();
+        assert_eq!(format.key(&path(&["inner", "cfg"])), "inner.cfg");
+        assert_eq!(
+            format.key(&path(&["backends[]", "host"])),
+            "backends[].host"
+        );
+        assert_eq!(
+            format.key(&path(&["servers", "<name>", "port"])),
+            "servers.<name>.port"
+        );
+
+        let format = KeyFormat {
+            delimiter: Some("/".to_string()),
+            prefix: Some("app".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(format.key(&path(&["inner", "cfg"])), "app/inner/cfg");
+    }
+
+    #[test]
+    fn env_defaults() {
+        let format = env(None, None, KeyCase::Upper);
+        assert_eq!(format.key(&path(&["database", "url"])), "DATABASE__URL");
+        assert_eq!(format.key(&path(&["log-level"])), "LOG_LEVEL");
+    }
+
+    #[test]
+    fn env_with_delimiter_and_prefix() {
+        let path = path(&["database", "max_connections"]);
+        assert_eq!(
+            env(Some("_"), None, KeyCase::Upper).key(&path),
+            "DATABASE_MAX_CONNECTIONS"
+        );
+        assert_eq!(
+            env(None, Some("app"), KeyCase::Upper).key(&path),
+            "APP__DATABASE__MAX_CONNECTIONS"
+        );
+        assert_eq!(
+            env(Some("_"), Some("my-app"), KeyCase::Upper).key(&path),
+            "MY_APP_DATABASE_MAX_CONNECTIONS"
+        );
+        assert_eq!(
+            env(Some("."), Some("app"), KeyCase::Lower).key(&path),
+            "app.database.max_connections"
+        );
+    }
+
+    #[test]
+    fn env_list_and_map_placeholders() {
+        let format = env(None, Some("app"), KeyCase::Upper);
+        assert_eq!(
+            format.key(&path(&["backends[]", "host"])),
+            "APP__BACKENDS__<INDEX>__HOST"
+        );
+        assert_eq!(
+            format.key(&path(&["servers", MAP_KEY_PLACEHOLDER, "port"])),
+            "APP__SERVERS__<NAME>__PORT"
+        );
+        assert_eq!(
+            format.key(&path(&[
+                "groups[]",
+                "members",
+                MAP_KEY_PLACEHOLDER,
+                "tags[]"
+            ])),
+            "APP__GROUPS__<INDEX>__MEMBERS__<NAME>__TAGS__<INDEX>"
+        );
+
+        let format = env(Some("_"), None, KeyCase::Lower);
+        assert_eq!(
+            format.key(&path(&["backends[]", "host"])),
+            "backends_<index>_host"
+        );
+        assert_eq!(
+            format.key(&path(&["servers", MAP_KEY_PLACEHOLDER, "port"])),
+            "servers_<name>_port"
+        );
+    }
+}
@@ -1,35 +1,58 @@
+// the derive on `Args` generates `::opt2doc::` paths
+extern crate self as opt2doc;
+
+#[cfg(feature = "cli")]
 mod args;
+#[cfg(feature = "cli")]
+mod artifact;
+#[cfg(feature = "cli")]
+mod check;
+#[cfg(feature = "cli")]
+mod compat;
+#[cfg(feature = "cli")]
+mod config;
+#[cfg(feature = "cli")]
+mod diff;
 
-use args::{Args, RenderFormat};
-use clap::Parser;
-use serde::{Deserialize, Serialize};
+#[cfg(feature = "cli")]
+use args::{Args, Command, RenderFormat, Transport};
+#[cfg(feature = "cli")]
+use config::ProjectConfig;
+use itertools::Itertools;
+use render::{build_tree, ConfigNode, NodeKind};
+use resolve::display_name;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+#[cfg(feature = "cli")]
 use std::{
-    collections::BTreeMap,
     fs::{create_dir_all, File},
     io::Write,
-    path::PathBuf,
+    path::Path,
 };
 
-use crate::socket::{get_socket_url, DocServerState};
+#[cfg(feature = "cli")]
+mod jsonl;
+mod key_format;
+mod opt2doc_trait;
+mod render;
+mod resolve;
+#[cfg(feature = "cli")]
+mod scan;
+#[cfg(feature = "cli")]
 mod socket;
+#[cfg(feature = "cli")]
+mod transport;
 
-pub use socket::DocClientState;
-
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct FieldMetadata {
-    pub name: Option<String>,
-    pub doc: Option<String>,
-    pub ty: Vec<String>,
-    pub default: Option<String>,
-    pub deprecated: Option<String>,
-}
-
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct CompsiteMetadata {
-    pub name: String,
-    pub doc: String,
-    pub fields: Vec<(String, FieldMetadata)>,
-}
+pub use key_format::{KeyCase, KeyFormat, KeyStyle};
+pub use opt2doc_core::{
+    Collection, CompsiteKind, CompsiteMetadata, DocClientState, FieldMetadata, SourceLocation,
+    TypeDescriptor, VariantMetadata,
+};
+#[doc(hidden)]
+pub use opt2doc_trait::__private;
+pub use opt2doc_trait::{collect_metadata, Opt2Doc};
 
 pub fn run_cargo_doc(repo: &PathBuf) {
     // first call cargo doc
@@ -45,22 +68,23 @@ pub fn run_cargo_doc(repo: &PathBuf) {
     }
 }
 
-pub fn run_main() {
-    let args = Args::parse();
-
-    let mut server = DocServerState::new(&get_socket_url());
+#[cfg(feature = "cli")]
+/// run `cargo doc` in `repo` and collect all metadata sent back through `transport`
+fn collect_from_cargo_doc(repo: &PathBuf, transport: Transport) -> Vec<CompsiteMetadata> {
+    let mut server = transport.receiver();
     // first run `cargo doc --clean` to make sure we have the latest doc
     std::process::Command::new("cargo")
         .arg("clean")
         .arg("--doc")
-        .current_dir(&args.repo)
+        .current_dir(repo)
         .spawn()
         .unwrap()
         .wait()
         .unwrap();
     let mut handle = std::process::Command::new("cargo")
         .arg("doc")
-        .current_dir(&args.repo)
+        .envs(server.envs())
+        .current_dir(repo)
         .spawn()
         .expect("`cargo doc` command failed to start");
     let mut ret = Vec::new();
@@ -72,143 +96,799 @@ pub fn run_main() {
             break;
         }
 
-        // first try to accept all incoming connections available
-        while server.try_accept().is_some() {}
-
-        ret.extend(server.try_recv());
+        ret.extend(server.poll());
+        std::thread::sleep(std::time::Duration::from_millis(10));
     }
+    ret.extend(server.finish());
+    ret
+}
 
-    // render
-    let render_output = match args.render {
-        RenderFormat::None => {
-            // no action needs
-            // early return if no need to render
-            return;
+#[cfg(feature = "cli")]
+/// collect metadata the way `args` asks for, by scanning sources, running `cargo doc` or reading
+/// a `metadata.json`, keeping only the packages asked for
+fn collect_items(args: &Args, project: &ProjectConfig) -> Vec<CompsiteMetadata> {
+    let mut items = if let Some(path) = &args.from_metadata {
+        match artifact::MetadataFile::load(path) {
+            Ok(file) => file.items,
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
         }
-        RenderFormat::Markdown => render_markdown(ret, &args.root),
-        RenderFormat::Toml | RenderFormat::Yaml | RenderFormat::Html => {
-            todo!("Not yet implemented")
+    } else if args.scan {
+        scan::scan_repo(&args.repo)
+    } else {
+        collect_from_cargo_doc(&args.repo, args.transport)
+    };
+    items.retain(|item| {
+        // metadata from older derive macros doesn't know its crate
+        let Some(crate_name) = &item.crate_name else {
+            return true;
+        };
+        let matches = |package: &String| package.replace('-', "_") == *crate_name;
+        (args.package.is_empty() || args.package.iter().any(matches))
+            && !args.exclude.iter().any(matches)
+    });
+    project.override_types(&mut items);
+    items
+}
+
+#[cfg(feature = "cli")]
+pub fn run_main() {
+    let (args, project) = match Args::parse_with_config() {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
     };
 
-    // place all markdown files on same directory with tmp file
-    for (filename, content) in render_output {
-        let full_path = args.output.clone().join(format!("{}.md", filename));
+    if let Some(Command::CheckConfig(check_args)) = &args.command {
+        let ok = run_check_config(&args, &project, &check_args.file);
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+    if let Some(Command::Diff(diff_args)) = &args.command {
+        let out = diff_args.out.as_deref();
+        if let Err(err) = diff::run_diff(&args, &project, &diff_args.from, &diff_args.to, out) {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Compat(compat_args)) = &args.command {
+        let ok = compat::run_compat(&args, &project, &compat_args.baseline, compat_args.update)
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                false
+            });
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    let ret = collect_items(&args, &project);
+    let items = index_and_warn(ret.clone());
+    // roots of the formats that are rendered, `--root` is used by those without their own
+    let roots = args
+        .render
+        .iter()
+        .filter_map(|format| {
+            project
+                .format
+                .get(format)
+                .and_then(|options| options.root.clone())
+                .or(args.root.clone())
+        })
+        .flatten()
+        .unique()
+        .collect::<Vec<_>>();
+    warn_unknown_roots(&items, &roots);
+
+    let mut rendered = render_all(&args, &project, &ret);
+    let metadata_path = args.output.join(artifact::METADATA_FILE_NAME);
+    // the metadata file records the tool version and source lines, so it's only checked when
+    // it is committed along with the rendered files
+    if !args.check || metadata_path.exists() {
+        rendered.push((metadata_path, artifact::MetadataFile::new(ret).to_json()));
+    }
+    if args.check && rendered.is_empty() {
+        eprintln!(
+            "\u{001b}[1;93mwarning\u{001b}[0m: nothing to check, pass the formats to check with \
+             `--render`"
+        );
+    }
+    if args.check {
+        let up_to_date = check_rendered(&rendered);
+        std::process::exit(if up_to_date { 0 } else { 1 });
+    }
+    // place all rendered files on same directory with tmp file
+    for (full_path, content) in rendered {
         create_dir_all(full_path.parent().unwrap()).unwrap();
         let mut file = File::create(full_path).unwrap();
         file.write_all(content.as_bytes()).unwrap();
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
-enum Value {
-    String(String),
-    Number(f64),
-    Bool(bool),
-    Array(Vec<Value>),
-    Object(BTreeMap<String, Value>),
+#[cfg(feature = "cli")]
+/// render `items` in every format asked for, returns the path and content of each file
+fn render_all(
+    args: &Args,
+    project: &ProjectConfig,
+    items: &[CompsiteMetadata],
+) -> Vec<(PathBuf, String)> {
+    let mut ret = Vec::new();
+    // `--render markdown,markdown` renders once
+    for format in args.render.iter().unique() {
+        let options = project.format.get(format).cloned().unwrap_or_default();
+        let roots = options.root.or(args.root.clone());
+        let output = options.output.unwrap_or(args.output.clone());
+        let items = items.to_vec();
+        let render_output = match format {
+            RenderFormat::None => {
+                // no action needs
+                continue;
+            }
+            RenderFormat::Markdown => render_markdown(items, &roots, &args.key_format()),
+            RenderFormat::Toml => render::toml::render_toml(items, &roots),
+            RenderFormat::Yaml => render::yaml::render_yaml(items, &roots),
+            RenderFormat::Html => render::html::render_html(items, &roots),
+            RenderFormat::JsonSchema => render::json_schema::render_json_schema(items, &roots),
+            RenderFormat::Env => {
+                let format = args.env_format();
+                let examples = render::env::render_env_example(items.clone(), &roots, &format);
+                ret.extend(examples.into_iter().map(|(filename, content)| {
+                    let filename = format!(
+                        "{}.{}",
+                        filename.replace("::", "."),
+                        render::env::ENV_EXAMPLE_EXTENSION
+                    );
+                    (output.join(filename), content)
+                }));
+                render::env::render_env(items, &roots, &format)
+            }
+        };
+        ret.extend(render_output.into_iter().map(|(filename, content)| {
+            let filename = format!("{}.{}", filename.replace("::", "."), format.extension());
+            (output.join(filename), content)
+        }));
+    }
+    ret
+}
+
+#[cfg(feature = "cli")]
+/// compare rendered files with the ones on disk, printing a unified diff of each that is
+/// missing or differs, returns false if any does
+///
+/// files on disk that are no longer rendered are not noticed
+fn check_rendered(rendered: &[(PathBuf, String)]) -> bool {
+    let mut stale = 0;
+    for (path, content) in rendered {
+        let (old, old_name) = match std::fs::read_to_string(path) {
+            Ok(old) => (old, path.display().to_string()),
+            Err(_) => (String::new(), "/dev/null".to_string()),
+        };
+        if old == *content {
+            continue;
+        }
+        stale += 1;
+        print!(
+            "{}",
+            similar::TextDiff::from_lines(&old, content)
+                .unified_diff()
+                .header(&old_name, &path.display().to_string())
+        );
+    }
+    if stale == 0 {
+        eprintln!("All {} rendered file(s) are up to date", rendered.len());
+    } else {
+        eprintln!(
+            "{} of {} rendered file(s) are out of date, run without `--check` (or with `--no-check`) to update them",
+            stale,
+            rendered.len()
+        );
+    }
+    stale == 0
+}
+
+#[cfg(feature = "cli")]
+/// check `file` against the root picked by `--root`, returns false if any error is found
+fn run_check_config(args: &Args, project: &ProjectConfig, file: &Path) -> bool {
+    let config = match check::load_config_file(file) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            return false;
+        }
+    };
+    let items = index_and_warn(collect_items(args, project));
+    let roots = find_root_items(&items, &args.root);
+    let root = match roots.as_slice() {
+        [root] => *root,
+        [] => {
+            eprintln!("No root option struct found, check `--root`");
+            return false;
+        }
+        _ => {
+            eprintln!(
+                "Found more than one root, pick one with `--root`: {}",
+                roots.iter().map(|root| &root.name).join(", ")
+            );
+            return false;
+        }
+    };
+
+    let diagnostics = check::check_config(root, &items, &config);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    let errors = diagnostics
+        .iter()
+        .filter(|d| d.severity == check::Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+    eprintln!(
+        "Checked {} against `{}`: {} error(s), {} warning(s)",
+        file.display(),
+        root.name,
+        errors,
+        warnings
+    );
+    errors == 0
 }
 
-/// Using toml_edit to generate example.toml with default values and comments
-fn render_toml(items: Vec<CompsiteMetadata>, required_root: &Option<String>) -> String {
-    todo!()
+/// items keyed by their [`CompsiteMetadata::qualified_name`], with the type of every field
+/// resolved to one of them
+fn index_items(items: Vec<CompsiteMetadata>) -> BTreeMap<String, CompsiteMetadata> {
+    let mut items = items
+        .into_iter()
+        .map(|item| (item.qualified_name(), item))
+        .collect();
+    resolve::resolve_items(&mut items);
+    items
 }
 
-/// returns a key-value pair of filename and markdown content
-fn render_markdown(
-    items: Vec<CompsiteMetadata>,
+#[cfg(feature = "cli")]
+/// [`index_items`], warning about anything that is documented differently than one may expect
+fn index_and_warn(items: Vec<CompsiteMetadata>) -> BTreeMap<String, CompsiteMetadata> {
+    let warn = |message: String| eprintln!("\u{001b}[1;93mwarning\u{001b}[0m: {}", message);
+    for (name, group) in &items.iter().into_group_map_by(|item| item.qualified_name()) {
+        let sources: BTreeSet<String> = group
+            .iter()
+            .filter_map(|item| item.source.as_ref())
+            .map(ToString::to_string)
+            .collect();
+        if sources.len() > 1 {
+            warn(format!(
+                "`{}` is defined more than once, only one is documented: {}",
+                name,
+                sources.iter().join(", ")
+            ));
+        }
+    }
+    let mut items = items
+        .into_iter()
+        .map(|item| (item.qualified_name(), item))
+        .collect();
+    for ambiguity in resolve::resolve_items(&mut items) {
+        warn(format!(
+            "{}, the field is documented as a plain value",
+            ambiguity
+        ));
+    }
+    for cycle in find_cycles(&items) {
+        warn(format!(
+            "recursive type, expansion stops at the reference: {}",
+            cycle
+        ));
+    }
+    items
+}
+
+/// find out all root items, which is items that are not referenced by any other items, or the
+/// items named in `required_roots` if specified
+///
+/// types that only reference each other, i.e. `A -> B -> A`, would all be left out, so the
+/// first of each such group becomes a root as well, while a requested type is a root wherever it
+/// is referenced from
+fn find_root_items<'a>(
+    items: &'a BTreeMap<String, CompsiteMetadata>,
     required_roots: &Option<Vec<String>>,
-) -> Vec<(String, String)> {
-    let items = items
+) -> Vec<&'a CompsiteMetadata> {
+    if let Some(required_roots) = required_roots {
+        return items
+            .values()
+            .filter(|item| !item.skip)
+            .filter(|item| is_named(item, required_roots))
+            .collect();
+    }
+
+    let mut roots: BTreeSet<&str> = items
+        .keys()
+        .filter(|name| {
+            // a type only referencing itself, i.e. a tree of rules, is still a root
+            !items
+                .iter()
+                .filter(|(other, _)| other != name)
+                .any(|(_, item)| references(item).any(|(_, ty)| ty == name.as_str()))
+        })
+        .map(String::as_str)
+        .collect();
+
+    let mut reached = BTreeSet::new();
+    for root in &roots {
+        reached.extend(reachable(items, root));
+    }
+    while let Some(name) = items
+        .keys()
+        .map(String::as_str)
+        .filter(|name| !reached.contains(name))
+        .find(|name| {
+            // not reached from another group that would be picked instead
+            let own = reachable(items, name);
+            items
+                .keys()
+                .map(String::as_str)
+                .filter(|other| !reached.contains(other) && !own.contains(other))
+                .all(|other| !reachable(items, other).contains(name))
+        })
+    {
+        roots.insert(name);
+        reached.extend(reachable(items, name));
+    }
+
+    roots
         .into_iter()
-        .map(|item| (item.name.clone(), item))
-        .collect::<BTreeMap<_, _>>();
+        .map(|name| &items[name])
+        .filter(|item| !item.skip)
+        .collect()
+}
+
+/// `item` is one of `names`, given by its name or qualified name
+fn is_named(item: &CompsiteMetadata, names: &[String]) -> bool {
+    names.contains(&item.name) || names.contains(&item.qualified_name())
+}
 
-    // find out all root items, which is items that are not
-    // referenced by any other items
-    let mut root_items = items
+#[cfg(feature = "cli")]
+/// warn about every name in `roots` that matches none of `items`, which would render nothing
+fn warn_unknown_roots(items: &BTreeMap<String, CompsiteMetadata>, roots: &[String]) {
+    for root in roots {
+        if !items
+            .values()
+            .any(|item| is_named(item, std::slice::from_ref(root)))
+        {
+            eprintln!(
+                "\u{001b}[1;93mwarning\u{001b}[0m: root `{}` matches no documented type",
+                root
+            );
+        }
+    }
+}
+
+/// key of every documented field of `item` whose type is another item, along with that item's
+/// key
+fn references(item: &CompsiteMetadata) -> impl Iterator<Item = (&String, &str)> {
+    item.fields
         .iter()
-        .filter(|(name, _)| {
-            let typ = name;
-            !items.iter().any(|(_, item)| {
-                item.fields
-                    .iter()
-                    .any(|(_, field)| field.ty.last().unwrap() == *typ)
-            })
-        })
-        .collect::<BTreeMap<_, _>>();
+        .filter(|(_, field)| !field.skip)
+        .filter_map(|(key, field)| Some((key, field.resolved.as_deref()?)))
+}
 
-    // filter root item if specified
-    if let Some(required_roots) = required_roots {
-        root_items.retain(|name, _| required_roots.contains(name));
+/// keys of all items reachable from `from`, including itself
+fn reachable<'a>(
+    items: &'a BTreeMap<String, CompsiteMetadata>,
+    from: &'a str,
+) -> BTreeSet<&'a str> {
+    let mut seen = BTreeSet::new();
+    let mut stack = vec![from];
+    while let Some(name) = stack.pop() {
+        let Some((name, item)) = items.get_key_value(name) else {
+            continue;
+        };
+        if seen.insert(name.as_str()) {
+            stack.extend(references(item).map(|(_, ty)| ty));
+        }
+    }
+    seen
+}
+
+#[cfg(feature = "cli")]
+/// every cycle of composites referencing each other, i.e. `Rule.children -> Rule`, each
+/// listed once starting from its first type by name
+fn find_cycles(items: &BTreeMap<String, CompsiteMetadata>) -> Vec<String> {
+    fn walk<'a>(
+        items: &'a BTreeMap<String, CompsiteMetadata>,
+        start: &'a str,
+        current: &'a str,
+        stack: &mut Vec<(&'a str, &'a String)>,
+        cycles: &mut Vec<String>,
+    ) {
+        let Some(item) = items.get(current) else {
+            return;
+        };
+        for (key, ty) in references(item) {
+            let Some((next, next_item)) = items.get_key_value(ty) else {
+                continue;
+            };
+            if next_item.is_enum() {
+                continue;
+            }
+            stack.push((current, key));
+            if next == start {
+                cycles.push(format!(
+                    "{} -> {}",
+                    stack
+                        .iter()
+                        .map(|(ty, key)| format!("{}.{}", items[*ty].name, key))
+                        .join(" -> "),
+                    items[start].name
+                ));
+            } else if next.as_str() > start && !stack.iter().any(|(ty, _)| ty == next) {
+                walk(items, start, next, stack, cycles);
+            }
+            stack.pop();
+        }
+    }
+
+    let mut cycles = Vec::new();
+    for start in items.keys() {
+        walk(items, start, start, &mut Vec::new(), &mut cycles);
     }
+    cycles
+}
 
-    // starting from root items, recursively find all items
+/// returns a key-value pair of filename and markdown content
+fn render_markdown(
+    items: Vec<CompsiteMetadata>,
+    required_roots: &Option<Vec<String>>,
+    format: &KeyFormat,
+) -> Vec<(String, String)> {
+    let items = index_items(items);
+    let root_items = find_root_items(&items, required_roots);
+
+    // starting from root items, list the keys of all nested items in one table
     let mut metadata: Vec<CompsiteMetadata> = Vec::new();
-    for (_, root) in root_items {
-        // recursively find compsite items and expand them into new_fields
+    for root in root_items {
         let mut expaned_root = root.clone();
         let mut new_fields: Vec<(String, FieldMetadata)> = Vec::new();
-        for (field_name, field) in &root.fields {
-            expand_recur(field_name, field, &mut new_fields, &items, ".");
-        }
+        flatten_tree(
+            &build_tree(root, &items),
+            &[],
+            &items,
+            format,
+            &mut new_fields,
+        );
 
         expaned_root.fields = new_fields;
         metadata.push(expaned_root);
     }
     metadata
         .iter()
-        .map(|item| (item.name.clone(), compsite_to_markdown(item)))
+        .map(|item| {
+            (
+                display_name(item, &items),
+                compsite_to_markdown(item, &items),
+            )
+        })
         .collect()
 }
 
 /// expand field with name delimitered by `delimiter`
 pub fn expand_recur(
-    field_name: &String,
+    field_name: &str,
     field: &FieldMetadata,
     new_fields: &mut Vec<(String, FieldMetadata)>,
     items: &BTreeMap<String, CompsiteMetadata>,
     delimiter: &str,
 ) {
-    // items's name is their type, so if it's in items, it's a compsite data
-    // so recursively find it's fields and append to new_fields
-    if let Some(compsite) = items.get(field.ty.last().unwrap()) {
-        // go through compsite's fields and expand them
-        for (inner_field_name, inner_field) in &compsite.fields {
-            let full_field_name = format!("{}{}{}", compsite.name, delimiter, inner_field_name);
-            expand_recur(&full_field_name, inner_field, new_fields, items, delimiter);
+    let format = KeyFormat {
+        delimiter: Some(delimiter.to_string()),
+        ..Default::default()
+    };
+    let compsite = CompsiteMetadata {
+        fields: vec![(field_name.to_string(), field.clone())],
+        ..Default::default()
+    };
+    flatten_tree(
+        &build_tree(&compsite, items),
+        &[],
+        items,
+        &format,
+        new_fields,
+    );
+}
+
+/// one field per key in `nodes` and the tables nested in them, keyed by its full path as the
+/// markdown table lists them, `path` is the key path of the table they are in
+///
+/// a key in an `Option` table is marked optional, as it can be left out along with the table
+fn flatten_tree(
+    nodes: &[ConfigNode],
+    path: &[String],
+    items: &BTreeMap<String, CompsiteMetadata>,
+    format: &KeyFormat,
+    new_fields: &mut Vec<(String, FieldMetadata)>,
+) {
+    for node in nodes {
+        let mut key_path = path.to_vec();
+        key_path.push(node.key.clone());
+        let mut field = node.field.clone();
+        field.optional |= node.inside_optional;
+        match &node.kind {
+            NodeKind::Value { .. } => new_fields.push((format.key(&key_path), field)),
+            NodeKind::Table { children, .. } => {
+                flatten_tree(children, &node.inner_path(path), items, format, new_fields)
+            }
+            // point to where its keys are listed instead
+            NodeKind::Recursive {
+                compsite,
+                collection,
+                target,
+            } => {
+                let target = if target.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![format.key(target)]
+                };
+                let link = format!(
+                    "[`{}`](#{})",
+                    compsite.name,
+                    display_name(compsite, items)
+                        .to_lowercase()
+                        .replace("::", "")
+                );
+                let note = render::recursive_note_naming(&link, *collection, &target);
+                field.doc = Some(match field.doc.as_deref() {
+                    Some(doc) if !doc.trim().is_empty() => format!("{}\n{}", doc, note),
+                    _ => note,
+                });
+                new_fields.push((format.key(&key_path), field));
+            }
         }
-    } else {
-        new_fields.push((field_name.to_string(), field.clone()));
     }
 }
 
-pub fn compsite_to_markdown(compsite: &CompsiteMetadata) -> String {
+/// `items` is used to look up the allowed values of enum typed fields
+pub fn compsite_to_markdown(
+    compsite: &CompsiteMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+) -> String {
     let mut output = String::new();
-    output.push_str(&format!("# {}\n", compsite.name));
+    output.push_str(&format!("# {}\n", display_name(compsite, items)));
     output.push_str(&format!("{}\n", compsite.doc));
 
-    let table_header = [
-        "| Key | Type | Default | Descriptions | Deprecated |\n",
-        "| --- | ---- | ------- | ------------ | ---------- |\n",
-    ];
-    output.push_str(&table_header.join(""));
+    if compsite.is_enum() {
+        output.push_str(&enum_to_markdown_table(compsite));
+        return output;
+    }
+
+    // only command line structs get the flags column, and only fields read from the
+    // environment the env column
+    let has_flags = compsite
+        .fields
+        .iter()
+        .any(|(_, field)| field.cli_flags().is_some());
+    let has_env = compsite.fields.iter().any(|(_, field)| field.env.is_some());
+    let mut columns = vec![("Key", "---")];
+    if has_flags {
+        columns.push(("Flags", "-----"));
+    }
+    if has_env {
+        columns.push(("Env", "---"));
+    }
+    columns.extend([
+        ("Type", "----"),
+        ("Required", "--------"),
+        ("Default", "-------"),
+        ("Descriptions", "------------"),
+        ("Deprecated", "----------"),
+    ]);
+    output.push_str(&format!(
+        "| {} |\n| {} |\n",
+        columns.iter().map(|(name, _)| name).join(" | "),
+        columns.iter().map(|(_, rule)| rule).join(" | ")
+    ));
     for (field_name, field) in compsite.clone().fields {
+        let mut key = field_name;
+        if has_flags {
+            key.push_str(&format!(
+                "|{}",
+                field
+                    .cli_flags()
+                    .map(|f| format!("`{}`", f))
+                    .unwrap_or("--".to_string())
+            ));
+        }
+        if has_env {
+            key.push_str(&format!("|{}", field.env.as_deref().unwrap_or("--")));
+        }
+        let mut doc = field.doc.clone().unwrap_or("--".to_string());
+        if let Some(enum_item) = field
+            .resolved
+            .as_deref()
+            .and_then(|key| items.get(key))
+            .filter(|item| item.is_enum())
+        {
+            let note = format!(
+                "Allowed values: {}",
+                enum_item
+                    .allowed_values_for(&field)
+                    .iter()
+                    .map(|v| format!("`{}`", v))
+                    .join(", ")
+            );
+            doc = match field.doc.as_deref() {
+                Some(doc) if !doc.trim().is_empty() => format!("{}\n{}", doc, note),
+                _ => note,
+            };
+        }
         output.push_str(&format!(
-            "|{}|{}|{}|{}|{}|\n",
-            field_name,
-            field.ty.join("."),
+            "|{}|`{}`|{}|{}|{}|{}|\n",
+            key,
+            field.ty,
+            if render::is_required(&field) {
+                "yes"
+            } else {
+                "no"
+            },
             escape_markdown_in_cell_newline(&field.default.unwrap_or("--".to_string())),
-            escape_markdown_in_cell_newline(&field.doc.unwrap_or("--".to_string())),
+            escape_markdown_in_cell_newline(&doc),
             escape_markdown_in_cell_newline(&field.deprecated.unwrap_or("--".to_string()))
         ));
     }
     output
 }
 
+/// list all variants of an enum, with the fields of data-carrying variants
+fn enum_to_markdown_table(compsite: &CompsiteMetadata) -> String {
+    let mut output = String::new();
+    let table_header = [
+        "| Variant | Fields | Descriptions | Deprecated |\n",
+        "| ------- | ------ | ------------ | ---------- |\n",
+    ];
+    output.push_str(&table_header.join(""));
+    for variant in compsite.variants.iter().filter(|v| !v.skip) {
+        let fields = if variant.fields.is_empty() {
+            "--".to_string()
+        } else {
+            variant
+                .fields
+                .iter()
+                .filter(|(_, field)| !field.skip)
+                .map(|(name, field)| format!("{}: `{}`", name, field.ty))
+                .join(", ")
+        };
+        output.push_str(&format!(
+            "|{}|{}|{}|{}|\n",
+            variant.name,
+            fields,
+            escape_markdown_in_cell_newline(&variant.doc),
+            escape_markdown_in_cell_newline(variant.deprecated.as_deref().unwrap_or("--"))
+        ));
+    }
+    output
+}
+
 /// Escape markdown in cell and replace newline with `  \\n`
 /// TODO: make this more robust
 fn escape_markdown_in_cell_newline(s: &str) -> String {
     s.replace('\n', "  \\n  ")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a struct whose fields are of the given types, `Vec<T>` is written `[T]`
+    fn item(name: &str, fields: &[(&str, &str)]) -> CompsiteMetadata {
+        CompsiteMetadata {
+            name: name.to_string(),
+            fields: fields
+                .iter()
+                .map(|(key, ty)| {
+                    let ty = match ty.strip_prefix('[').and_then(|ty| ty.strip_suffix(']')) {
+                        Some(elem) => TypeDescriptor::Path {
+                            segments: vec!["Vec".to_string()],
+                            args: vec![TypeDescriptor::named(elem)],
+                        },
+                        None => TypeDescriptor::named(ty),
+                    };
+                    let field = FieldMetadata {
+                        ty,
+                        ..Default::default()
+                    };
+                    (key.to_string(), field)
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn root_names(
+        items: &BTreeMap<String, CompsiteMetadata>,
+        required_roots: Option<&[&str]>,
+    ) -> Vec<String> {
+        let required_roots =
+            required_roots.map(|roots| roots.iter().map(ToString::to_string).collect());
+        find_root_items(items, &required_roots)
+            .into_iter()
+            .map(|item| item.name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn roots_are_the_unreferenced_items() {
+        let items = index_items(vec![
+            item("Config", &[("db", "Db"), ("backends", "[Backend]")]),
+            item("Db", &[("url", "String")]),
+            item("Backend", &[("host", "String")]),
+            item("Other", &[]),
+        ]);
+        assert_eq!(root_names(&items, None), ["Config", "Other"]);
+    }
+
+    #[test]
+    fn self_referencing_item_is_a_root() {
+        let items = index_items(vec![item("Rule", &[("children", "[Rule]")])]);
+        assert_eq!(root_names(&items, None), ["Rule"]);
+    }
+
+    #[test]
+    fn first_of_a_cycle_is_a_root() {
+        let items = index_items(vec![
+            item("Mutual", &[("back", "Cyclic")]),
+            item("Cyclic", &[("other", "Mutual")]),
+        ]);
+        assert_eq!(root_names(&items, None), ["Cyclic"]);
+    }
+
+    #[test]
+    fn cycle_reached_from_a_root_adds_no_root() {
+        let items = index_items(vec![
+            item("Config", &[("a", "A")]),
+            item("A", &[("b", "B")]),
+            item("B", &[("a", "A")]),
+        ]);
+        assert_eq!(root_names(&items, None), ["Config"]);
+    }
+
+    #[test]
+    fn requested_roots_are_kept_even_in_a_cycle() {
+        let items = index_items(vec![
+            item("Mutual", &[("back", "Cyclic")]),
+            item("Cyclic", &[("other", "Mutual")]),
+            item("Config", &[("db", "Db")]),
+            item("Db", &[]),
+        ]);
+        assert_eq!(root_names(&items, Some(&["Mutual"])), ["Mutual"]);
+        assert_eq!(
+            root_names(&items, Some(&["Db", "Config"])),
+            ["Config", "Db"]
+        );
+        assert!(root_names(&items, Some(&["Missing"])).is_empty());
+    }
+
+    #[test]
+    fn skipped_items_are_never_roots() {
+        let mut skipped = item("Hidden", &[]);
+        skipped.skip = true;
+        let items = index_items(vec![skipped, item("Config", &[])]);
+        assert_eq!(root_names(&items, None), ["Config"]);
+        assert!(root_names(&items, Some(&["Hidden"])).is_empty());
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn cycles_are_listed_once_from_their_first_type() {
+        let items = index_items(vec![
+            item("Rule", &[("children", "[Rule]")]),
+            item("Mutual", &[("back", "Cyclic")]),
+            item("Cyclic", &[("other", "Mutual")]),
+            item("Config", &[("rule", "Rule")]),
+        ]);
+        assert_eq!(
+            find_cycles(&items),
+            [
+                "Cyclic.other -> Mutual.back -> Cyclic",
+                "Rule.children -> Rule"
+            ]
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{render_markdown, CompsiteMetadata};
+
+/// Implemented by `#[derive(Opt2Doc)]`, gives access to the documentation metadata at runtime,
+/// without `cargo doc` or `cargo opt2doc`.
+///
+/// i.e. a `--print-config-docs` flag can simply be
+///
+/// `println!("{}", Config::markdown());`
+pub trait Opt2Doc {
+    /// metadata of this type alone
+    fn metadata() -> CompsiteMetadata;
+
+    /// push metadata of all derived types referred by this type's fields,
+    /// generated by the derive macro
+    fn collect_nested(_out: &mut Vec<CompsiteMetadata>) {}
+
+    /// metadata of this type followed by every derived type reachable from it,
+    /// each type appears only once
+    fn all_metadata() -> Vec<CompsiteMetadata>
+    where
+        Self: Sized,
+    {
+        let mut out = Vec::new();
+        collect_metadata::<Self>(&mut out);
+        out
+    }
+
+    /// render this type as root into a markdown table
+    fn markdown() -> String
+    where
+        Self: Sized,
+    {
+        let root = Self::metadata().qualified_name();
+        render_markdown(Self::all_metadata(), &Some(vec![root]), &Default::default())
+            .into_iter()
+            .map(|(_, content)| content)
+            .collect()
+    }
+}
+
+/// push `T` and everything reachable from it, stop at types already collected so recursive types
+/// terminate
+pub fn collect_metadata<T: Opt2Doc>(out: &mut Vec<CompsiteMetadata>) {
+    let metadata = T::metadata();
+    let name = metadata.qualified_name();
+    if out.iter().any(|item| item.qualified_name() == name) {
+        return;
+    }
+    out.push(metadata);
+    T::collect_nested(out);
+}
+
+/// Used by the derive macro only, not part of the public API.
+///
+/// The derive doesn't know which field types implement [`Opt2Doc`], so every type in a field is
+/// wrapped in a `Probe` and autoref specialization picks `ViaOpt2Doc` when it's implemented,
+/// otherwise the no-op `ViaFallback`.
+#[doc(hidden)]
+pub mod __private {
+    use std::marker::PhantomData;
+
+    use super::{collect_metadata, Opt2Doc};
+    use crate::CompsiteMetadata;
+
+    pub struct Probe<T: ?Sized>(PhantomData<T>);
+
+    impl<T: ?Sized> Probe<T> {
+        #[allow(clippy::new_without_default)]
+        pub fn new() -> Self {
+            Self(PhantomData)
+        }
+    }
+
+    pub trait ViaOpt2Doc {
+        fn collect_into(&self, out: &mut Vec<CompsiteMetadata>);
+    }
+
+    impl<T: Opt2Doc> ViaOpt2Doc for &Probe<T> {
+        fn collect_into(&self, out: &mut Vec<CompsiteMetadata>) {
+            collect_metadata::<T>(out)
+        }
+    }
+
+    pub trait ViaFallback {
+        fn collect_into(&self, out: &mut Vec<CompsiteMetadata>);
+    }
+
+    impl<T: ?Sized> ViaFallback for Probe<T> {
+        fn collect_into(&self, _out: &mut Vec<CompsiteMetadata>) {}
+    }
+
+    /// the derive embeds metadata as a json string, same as what `doc_impl` receives
+    pub fn from_json(s: &str) -> CompsiteMetadata {
+        serde_json::from_str(s).expect("metadata generated by `Opt2Doc` derive should be valid")
+    }
+
+    /// `module_path!()` at the type's definition, which unlike the file also knows inline
+    /// `mod` blocks
+    pub fn with_module_path(mut metadata: CompsiteMetadata, path: &str) -> CompsiteMetadata {
+        let (crate_name, module) = path.split_once("::").unwrap_or((path, ""));
+        metadata.crate_name = Some(crate_name.to_string());
+        metadata.module_path = Some(module.to_string());
+        metadata
+    }
+}
@@ -0,0 +1,233 @@
+//! Render the environment variables of every option, as read by figment or config-rs with an
+//! env prefix and `__` between nested keys.
+//!
+//! Each root gets a markdown table and a commented `.env.example`, variables with a default are
+//! set to it, the others are commented out with a placeholder.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use super::{
+    allowed_values_note, build_tree, placeholder_value, recursive_note, ConfigNode, NodeKind,
+    REQUIRED_NOTE,
+};
+use crate::{
+    escape_markdown_in_cell_newline, find_root_items, index_items, resolve::display_name,
+    CompsiteMetadata, KeyFormat,
+};
+
+/// extension of the example file, next to the `.env.md` table
+pub const ENV_EXAMPLE_EXTENSION: &str = "env.example";
+
+/// An option as an environment variable
+struct EnvVar<'a> {
+    name: String,
+    node: &'a ConfigNode<'a>,
+    /// said of the variable besides its doc, i.e. the allowed values
+    notes: Vec<String>,
+    /// the value can be set as a whole, recursive tables only have their keys documented
+    settable: bool,
+}
+
+/// returns a key-value pair of filename and markdown table, one per root
+pub fn render_env(
+    items: Vec<CompsiteMetadata>,
+    required_roots: &Option<Vec<String>>,
+    format: &KeyFormat,
+) -> Vec<(String, String)> {
+    let items = index_items(items);
+    find_root_items(&items, required_roots)
+        .into_iter()
+        .map(|root| {
+            (
+                display_name(root, &items),
+                compsite_to_env_markdown(root, &items, format),
+            )
+        })
+        .collect()
+}
+
+/// returns a key-value pair of filename and `.env.example` content, one per root
+pub fn render_env_example(
+    items: Vec<CompsiteMetadata>,
+    required_roots: &Option<Vec<String>>,
+    format: &KeyFormat,
+) -> Vec<(String, String)> {
+    let items = index_items(items);
+    find_root_items(&items, required_roots)
+        .into_iter()
+        .map(|root| {
+            (
+                display_name(root, &items),
+                compsite_to_env_example(root, &items, format),
+            )
+        })
+        .collect()
+}
+
+pub fn compsite_to_env_markdown(
+    compsite: &CompsiteMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+    format: &KeyFormat,
+) -> String {
+    let mut output = String::new();
+    output.push_str(&format!("# {}\n", display_name(compsite, items)));
+    output.push_str(&format!("{}\n", compsite.doc));
+    output.push_str("| Variable | Type | Default | Description |\n");
+    output.push_str("| -------- | ---- | ------- | ----------- |\n");
+    let tree = build_tree(compsite, items);
+    let mut vars = Vec::new();
+    collect_vars(&tree, &[], format, &mut vars);
+    for var in vars {
+        let mut description = vec![var.node.doc().to_string()];
+        description.extend(var.notes);
+        let description = description
+            .into_iter()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        output.push_str(&format!(
+            "|`{}`|`{}`|{}|{}|\n",
+            var.name,
+            var.node.field.ty,
+            escape_markdown_in_cell_newline(var.node.field.default.as_deref().unwrap_or("--")),
+            escape_markdown_in_cell_newline(if description.is_empty() {
+                "--"
+            } else {
+                &description
+            })
+        ));
+    }
+    output
+}
+
+pub fn compsite_to_env_example(
+    compsite: &CompsiteMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+    format: &KeyFormat,
+) -> String {
+    let mut output = String::new();
+    push_comment(&mut output, &compsite.doc);
+    let tree = build_tree(compsite, items);
+    let mut vars = Vec::new();
+    collect_vars(&tree, &[], format, &mut vars);
+    for var in vars {
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        push_comment(&mut output, var.node.doc());
+        for note in &var.notes {
+            push_comment(&mut output, note);
+        }
+        if !var.settable {
+            continue;
+        }
+        match &var.node.field.default {
+            // names of list elements and map entries are templates, the user picks the index
+            // or name
+            Some(default) if !var.name.contains('<') => {
+                output.push_str(&format!("{}={}\n", var.name, env_value(default)))
+            }
+            Some(default) => output.push_str(&format!("# {}={}\n", var.name, env_value(default))),
+            None => {
+                let enum_item = match var.node.kind {
+                    NodeKind::Value { enum_item } => enum_item,
+                    _ => None,
+                };
+                let placeholder = match placeholder_value(var.node.field, enum_item) {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                output.push_str(&format!("# {}={}\n", var.name, env_value(&placeholder)));
+            }
+        }
+    }
+    output
+}
+
+/// every value in `nodes` and the tables nested in them, `path` is the key path of the table
+/// they are in
+fn collect_vars<'a>(
+    nodes: &'a [ConfigNode<'a>],
+    path: &[String],
+    format: &KeyFormat,
+    vars: &mut Vec<EnvVar<'a>>,
+) {
+    for node in nodes {
+        let mut notes = Vec::new();
+        match &node.kind {
+            NodeKind::Value { enum_item } => {
+                let mut key_path = path.to_vec();
+                key_path.push(node.key.clone());
+                // `#[opt2doc(env = "...")]` and clap's `env` are the full name already
+                let name = node
+                    .field
+                    .env
+                    .clone()
+                    .unwrap_or_else(|| format.key(&key_path));
+                notes.extend(allowed_values_note(node.field, *enum_item));
+                if node.is_required() {
+                    notes.push(REQUIRED_NOTE.to_string());
+                }
+                notes.extend(deprecation_note(node));
+                vars.push(EnvVar {
+                    name,
+                    node,
+                    notes,
+                    settable: true,
+                });
+            }
+            NodeKind::Table { children, .. } => {
+                collect_vars(children, &node.inner_path(path), format, vars);
+            }
+            NodeKind::Recursive {
+                compsite,
+                collection,
+                target,
+            } => {
+                let target = if target.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![format.key(target)]
+                };
+                notes.push(recursive_note(compsite, *collection, &target));
+                notes.extend(deprecation_note(node));
+                vars.push(EnvVar {
+                    name: format.key(&node.inner_path(path)),
+                    node,
+                    notes,
+                    settable: false,
+                });
+            }
+        }
+    }
+}
+
+fn deprecation_note(node: &ConfigNode) -> Option<String> {
+    node.field
+        .deprecation()
+        .map(|deprecation| format!("WARNING: {}", deprecation))
+}
+
+/// `value` as written in a `.env` file, quoted unless it is a single plain word
+fn env_value(value: &str) -> String {
+    let plain = value
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_./:,@".contains(c));
+    if plain {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+fn push_comment(output: &mut String, doc: &str) {
+    for line in doc.lines() {
+        if line.trim().is_empty() {
+            output.push_str("#\n");
+        } else {
+            output.push_str(&format!("# {}\n", line));
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Render every root into one self-contained HTML page, with a filter box, collapsible
+//! sections and an anchor for every dotted key path.
+
+use std::collections::BTreeMap;
+
+use super::{
+    allowed_values_note, build_tree, default_value, is_required, recursive_note, ConfigNode,
+    NodeKind, MAP_KEY_PLACEHOLDER,
+};
+use crate::{find_root_items, index_items, resolve::display_name, CompsiteMetadata};
+
+/// file name of the page, without extension
+pub const HTML_PAGE_NAME: &str = "options";
+
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 72em; padding: 0 1em; }
+#filter { width: 100%; padding: .5em; font-size: 1em; margin-bottom: 1em; box-sizing: border-box; }
+details { margin-bottom: 1em; }
+summary { font-size: 1.4em; font-weight: bold; cursor: pointer; }
+table { border-collapse: collapse; width: 100%; margin-top: .5em; }
+th, td { border: 1px solid #ccc; padding: .3em .6em; text-align: left; vertical-align: top; }
+td.doc { white-space: pre-wrap; }
+tr:target { background: #fff3b0; }
+a.anchor { text-decoration: none; color: #999; margin-right: .3em; }
+.badge { border-radius: .3em; padding: 0 .4em; font-size: .8em; color: #fff; }
+.deprecated { background: #c0392b; }
+.required { background: #2c6fbb; }
+"#;
+
+const SCRIPT: &str = r#"
+document.getElementById("filter").addEventListener("input", function (e) {
+  var needle = e.target.value.toLowerCase();
+  document.querySelectorAll("details").forEach(function (section) {
+    var shown = 0;
+    section.querySelectorAll("tbody tr").forEach(function (row) {
+      var hit = row.textContent.toLowerCase().indexOf(needle) !== -1;
+      row.style.display = hit ? "" : "none";
+      if (hit) { shown += 1; }
+    });
+    section.style.display = shown > 0 || needle === "" ? "" : "none";
+    if (needle !== "") { section.open = shown > 0; }
+  });
+});
+"#;
+
+/// returns a single pair of filename and html content with all roots in it
+pub fn render_html(
+    items: Vec<CompsiteMetadata>,
+    required_roots: &Option<Vec<String>>,
+) -> Vec<(String, String)> {
+    let items = index_items(items);
+    let roots = find_root_items(&items, required_roots);
+
+    let mut output = String::new();
+    output.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    output.push_str("<title>Options</title>\n");
+    output.push_str(&format!("<style>{}</style>\n", STYLE));
+    output.push_str("</head>\n<body>\n");
+    output.push_str("<input id=\"filter\" type=\"search\" placeholder=\"Filter options...\">\n");
+    for root in roots {
+        output.push_str(&compsite_to_html(root, &items));
+    }
+    output.push_str(&format!("<script>{}</script>\n", SCRIPT));
+    output.push_str("</body>\n</html>\n");
+    vec![(HTML_PAGE_NAME.to_string(), output)]
+}
+
+/// one collapsible section for a root
+pub fn compsite_to_html(
+    compsite: &CompsiteMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+) -> String {
+    let name = display_name(compsite, items);
+    let mut output = String::new();
+    output.push_str(&format!(
+        "<details open id=\"{}\">\n<summary>{}</summary>\n",
+        escape_html(&name),
+        escape_html(&name)
+    ));
+    if !compsite.doc.trim().is_empty() {
+        output.push_str(&format!("<p>{}</p>\n", escape_html(&compsite.doc)));
+    }
+    output.push_str("<table>\n<thead><tr><th>Key</th><th>Type</th><th>Required</th><th>Default</th><th>Description</th></tr></thead>\n<tbody>\n");
+    write_rows(&mut output, &name, &[], &build_tree(compsite, items));
+    output.push_str("</tbody>\n</table>\n</details>\n");
+    output
+}
+
+fn write_rows(output: &mut String, root: &str, path: &[String], nodes: &[ConfigNode]) {
+    for node in nodes {
+        let mut key_path = path.to_vec();
+        key_path.push(node.key.clone());
+        let dotted = key_path.join(".");
+        let anchor = format!("{}.{}", root, dotted);
+
+        let mut doc = escape_html(node.doc());
+        match &node.kind {
+            NodeKind::Value { enum_item } => {
+                if let Some(note) = allowed_values_note(node.field, *enum_item) {
+                    doc.push_str(&format!("\n{}", escape_html(&note)));
+                }
+            }
+            // link back to where the keys are documented
+            NodeKind::Recursive {
+                compsite,
+                collection,
+                target,
+            } => {
+                let table = table_path(target);
+                let href = if table.is_empty() {
+                    root.to_string()
+                } else {
+                    format!("{}.{}", root, table.join("."))
+                };
+                doc.push_str(&format!(
+                    "\n<a href=\"#{}\">{}</a>",
+                    escape_html(&href),
+                    escape_html(&recursive_note(compsite, *collection, target))
+                ));
+            }
+            NodeKind::Table { .. } => {}
+        }
+        let mut key = format!(
+            "<a class=\"anchor\" href=\"#{}\">#</a><code>{}</code>",
+            escape_html(&anchor),
+            escape_html(&dotted)
+        );
+        if let Some(deprecation) = node.field.deprecation() {
+            key.push_str(&format!(
+                " <span class=\"badge deprecated\" title=\"{}\">deprecated</span>",
+                escape_html(&deprecation)
+            ));
+        }
+        let required = if is_required(node.field) {
+            "<span class=\"badge required\">required</span>"
+        } else {
+            "--"
+        };
+        let default = default_value(node.field)
+            .map(|v| format!("<code>{}</code>", escape_html(&v.to_string())))
+            .unwrap_or_else(|| "--".to_string());
+
+        output.push_str(&format!(
+            "<tr id=\"{}\"><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td><td class=\"doc\">{}</td></tr>\n",
+            escape_html(&anchor),
+            key,
+            escape_html(&node.field.ty.to_string()),
+            required,
+            default,
+            doc
+        ));
+
+        if let NodeKind::Table { children, .. } = &node.kind {
+            write_rows(output, root, &node.inner_path(path), children);
+        }
+    }
+}
+
+/// path of the row of the table whose keys are at `path`, i.e. `nodes` for `nodes[]` and
+/// `backends` for `backends.<name>`, as elements and entries have no row of their own
+fn table_path(path: &[String]) -> Vec<String> {
+    let mut path = path.to_vec();
+    match path.last_mut() {
+        Some(last) if last == MAP_KEY_PLACEHOLDER => {
+            path.pop();
+        }
+        Some(last) => {
+            if let Some(list) = last.strip_suffix("[]") {
+                *last = list.to_string();
+            }
+        }
+        None => {}
+    }
+    path
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
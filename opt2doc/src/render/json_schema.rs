@@ -0,0 +1,354 @@
+//! Render a draft 2020-12 JSON Schema per root, which editors (i.e. taplo for TOML,
+//! yaml-language-server for YAML) use for completion, inline docs and validation.
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map, Value};
+
+use super::{
+    build_tree, default_value, is_optional, is_required, value_kind, ConfigNode, NodeKind,
+    ValueKind,
+};
+use crate::{
+    find_root_items, index_items, resolve::display_name, Collection, CompsiteMetadata,
+    FieldMetadata,
+};
+
+pub const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// returns a key-value pair of filename and schema content, one per root
+pub fn render_json_schema(
+    items: Vec<CompsiteMetadata>,
+    required_roots: &Option<Vec<String>>,
+) -> Vec<(String, String)> {
+    let items = index_items(items);
+    find_root_items(&items, required_roots)
+        .into_iter()
+        .map(|root| {
+            let schema = compsite_to_json_schema(root, &items);
+            (
+                format!("{}.schema", display_name(root, &items)),
+                serde_json::to_string_pretty(&schema).unwrap(),
+            )
+        })
+        .collect()
+}
+
+pub fn compsite_to_json_schema(
+    compsite: &CompsiteMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+) -> Value {
+    let mut schema = Map::new();
+    schema.insert("$schema".to_string(), JSON_SCHEMA_DIALECT.into());
+    schema.insert("title".to_string(), compsite.name.clone().into());
+    schema.extend(object_schema(
+        &compsite.doc,
+        &build_tree(compsite, items),
+        &Scope {
+            path: Vec::new(),
+            pointer: "#".to_string(),
+            parent: None,
+            items,
+        },
+    ));
+    Value::Object(schema)
+}
+
+/// An object schema being rendered, recursive nodes refer back to one of these by `$ref`
+struct Scope<'a> {
+    /// dotted path of the keys inside, see [`ConfigNode::inner_path`]
+    path: Vec<String>,
+    /// json pointer to the object schema
+    pointer: String,
+    parent: Option<&'a Scope<'a>>,
+    /// to describe the fields of data carrying enum variants
+    items: &'a BTreeMap<String, CompsiteMetadata>,
+}
+
+impl Scope<'_> {
+    fn find(&self, path: &[String]) -> Option<&str> {
+        if self.path == path {
+            Some(&self.pointer)
+        } else {
+            self.parent?.find(path)
+        }
+    }
+}
+
+fn object_schema(doc: &str, nodes: &[ConfigNode], scope: &Scope) -> Map<String, Value> {
+    let mut schema = Map::new();
+    schema.insert("type".to_string(), "object".into());
+    insert_description(&mut schema, doc);
+
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for node in nodes {
+        let pointer = format!("{}/properties/{}", scope.pointer, escape_pointer(&node.key));
+        properties.insert(
+            node.key.clone(),
+            Value::Object(node_schema(node, scope, pointer)),
+        );
+        if is_required(node.field) {
+            required.push(Value::from(node.key.clone()));
+        }
+    }
+    schema.insert("properties".to_string(), Value::Object(properties));
+    if !required.is_empty() {
+        schema.insert("required".to_string(), Value::Array(required));
+    }
+    schema
+}
+
+/// `pointer` points at the schema of `node` itself
+fn node_schema(node: &ConfigNode, scope: &Scope, pointer: String) -> Map<String, Value> {
+    let mut schema = match &node.kind {
+        NodeKind::Table {
+            children,
+            collection,
+            ..
+        } => {
+            let element_pointer = match collection {
+                None => pointer,
+                Some(Collection::List) => format!("{}/items", pointer),
+                Some(Collection::Map) => format!("{}/additionalProperties", pointer),
+            };
+            let inner = Scope {
+                path: node.inner_path(&scope.path),
+                pointer: element_pointer,
+                parent: Some(scope),
+                items: scope.items,
+            };
+            match collection {
+                None => object_schema(node.doc(), children, &inner),
+                Some(collection) => {
+                    let element = object_schema("", children, &inner);
+                    let mut schema = collection_schema(*collection, element);
+                    insert_description(&mut schema, node.doc());
+                    schema
+                }
+            }
+        }
+        NodeKind::Recursive {
+            collection, target, ..
+        } => {
+            let mut element = Map::new();
+            element.insert("$ref".to_string(), scope.find(target).unwrap_or("#").into());
+            let mut schema = match collection {
+                None => element,
+                Some(collection) => collection_schema(*collection, element),
+            };
+            insert_description(&mut schema, node.doc());
+            schema
+        }
+        NodeKind::Value { enum_item } => {
+            let mut schema = match enum_item {
+                Some(enum_item) => enum_schema(enum_item, node.field, scope.items, &[]),
+                None => value_schema(node.field),
+            };
+            insert_description(&mut schema, node.doc());
+            if let Some(default) = default_value(node.field) {
+                schema.insert("default".to_string(), default);
+            }
+            schema
+        }
+    };
+    if node.field.deprecation().is_some() {
+        schema.insert("deprecated".to_string(), true.into());
+    }
+    // `null` (`~` in yaml) leaves an `Option` unset, as `check-config` accepts it
+    if is_optional(node.field) {
+        schema = nullable(schema);
+    }
+    schema
+}
+
+/// `schema` also accepting `null`
+fn nullable(mut schema: Map<String, Value>) -> Map<String, Value> {
+    if let Some(Value::String(ty)) = schema.get("type") {
+        let ty = json!([ty, "null"]);
+        schema.insert("type".to_string(), ty);
+    } else if let Some(Value::Array(values)) = schema.get_mut("enum") {
+        values.push(Value::Null);
+    } else if let Some(Value::Array(any_of)) = schema.get_mut("anyOf") {
+        any_of.push(json!({ "type": "null" }));
+    } else {
+        // a `$ref`, annotations stay next to the `anyOf` so editors still show them
+        let mut outer = Map::new();
+        for key in ["description", "default", "deprecated"] {
+            if let Some(value) = schema.remove(key) {
+                outer.insert(key.to_string(), value);
+            }
+        }
+        outer.insert(
+            "anyOf".to_string(),
+            json!([Value::Object(schema), { "type": "null" }]),
+        );
+        return outer;
+    }
+    schema
+}
+
+/// an array of, or an object with any keys mapping to, `element`
+fn collection_schema(collection: Collection, element: Map<String, Value>) -> Map<String, Value> {
+    let mut schema = Map::new();
+    match collection {
+        Collection::List => {
+            schema.insert("type".to_string(), "array".into());
+            schema.insert("items".to_string(), Value::Object(element));
+        }
+        Collection::Map => {
+            schema.insert("type".to_string(), "object".into());
+            schema.insert("additionalProperties".to_string(), Value::Object(element));
+        }
+    }
+    schema
+}
+
+/// escape a key as a json pointer token
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+fn value_schema(field: &FieldMetadata) -> Map<String, Value> {
+    let mut schema = Map::new();
+    let ty = match value_kind(field) {
+        ValueKind::Bool => "boolean",
+        ValueKind::Integer => "integer",
+        ValueKind::Float => "number",
+        ValueKind::String => "string",
+        ValueKind::Array => "array",
+        ValueKind::Map => "object",
+    };
+    schema.insert("type".to_string(), ty.into());
+    schema
+}
+
+/// unit variants are plain strings, data carrying variants are objects keyed by the variant
+/// name, which is how serde represents enums by default
+///
+/// `visiting` are the enums this one is nested in through variant fields, which are not
+/// described again
+fn enum_schema(
+    enum_item: &CompsiteMetadata,
+    field: &FieldMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+    visiting: &[String],
+) -> Map<String, Value> {
+    let names = enum_item.allowed_values_for(field);
+    let variants = enum_item.variants.iter().filter(|v| !v.skip);
+    let mut visiting = visiting.to_vec();
+    visiting.push(enum_item.qualified_name());
+    let mut unit = Vec::new();
+    let mut with_data = Vec::new();
+    for (variant, name) in variants.zip(names) {
+        if variant.fields.is_empty() {
+            unit.push(Value::from(name));
+        } else {
+            let mut content = variant_schema(&variant.fields, items, &visiting);
+            insert_description(&mut content, &variant.doc);
+            with_data.push(json!({
+                "type": "object",
+                "properties": { name.clone(): content },
+                "required": [name],
+                "additionalProperties": false,
+            }));
+        }
+    }
+
+    let mut schema = Map::new();
+    if with_data.is_empty() {
+        schema.insert("enum".to_string(), Value::Array(unit));
+    } else {
+        let mut any_of = Vec::new();
+        if !unit.is_empty() {
+            any_of.push(json!({ "enum": unit }));
+        }
+        any_of.extend(with_data);
+        schema.insert("anyOf".to_string(), Value::Array(any_of));
+    }
+    schema
+}
+
+/// the value under a data carrying variant's name, the field itself for a newtype variant, an
+/// array for a tuple variant and an object for a struct variant
+fn variant_schema(
+    fields: &[(String, FieldMetadata)],
+    items: &BTreeMap<String, CompsiteMetadata>,
+    visiting: &[String],
+) -> Map<String, Value> {
+    let fields: Vec<_> = fields.iter().filter(|(_, field)| !field.skip).collect();
+    // tuple fields are named by their index
+    let tuple = fields
+        .iter()
+        .enumerate()
+        .all(|(idx, (name, _))| *name == idx.to_string());
+    let schemas = fields
+        .iter()
+        .map(|(_, field)| Value::Object(variant_field_schema(field, items, visiting)));
+    let mut schema = Map::new();
+    match (tuple, fields.len()) {
+        (true, 1) => return variant_field_schema(&fields[0].1, items, visiting),
+        (true, len) => {
+            schema.insert("type".to_string(), "array".into());
+            schema.insert("prefixItems".to_string(), schemas.collect());
+            schema.insert("minItems".to_string(), len.into());
+            schema.insert("maxItems".to_string(), len.into());
+        }
+        (false, _) => {
+            let properties = fields
+                .iter()
+                .map(|(name, _)| name.clone())
+                .zip(schemas)
+                .collect();
+            let required: Vec<Value> = fields
+                .iter()
+                .filter(|(_, field)| is_required(field))
+                .map(|(name, _)| Value::from(name.clone()))
+                .collect();
+            schema.insert("type".to_string(), "object".into());
+            schema.insert("properties".to_string(), Value::Object(properties));
+            if !required.is_empty() {
+                schema.insert("required".to_string(), Value::Array(required));
+            }
+        }
+    }
+    schema
+}
+
+/// nested structs are only described as objects, their keys are documented with the struct
+fn variant_field_schema(
+    field: &FieldMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+    visiting: &[String],
+) -> Map<String, Value> {
+    let item = field
+        .resolved
+        .as_deref()
+        .and_then(|key| items.get_key_value(key))
+        .filter(|_| field.ty.item_type().0.is_none());
+    let mut schema = match item {
+        Some((key, item)) if item.is_enum() && !visiting.contains(key) => {
+            enum_schema(item, field, items, visiting)
+        }
+        Some((_, item)) if !item.is_enum() => {
+            let mut schema = Map::new();
+            schema.insert("type".to_string(), "object".into());
+            schema
+        }
+        _ => value_schema(field),
+    };
+    insert_description(&mut schema, field.doc.as_deref().unwrap_or_default());
+    if let Some(default) = default_value(field) {
+        schema.insert("default".to_string(), default);
+    }
+    if is_optional(field) {
+        schema = nullable(schema);
+    }
+    schema
+}
+
+fn insert_description(schema: &mut Map<String, Value>, doc: &str) {
+    if !doc.trim().is_empty() {
+        schema.insert("description".to_string(), doc.into());
+    }
+}
@@ -0,0 +1,331 @@
+//! Renderers other than markdown, which all work on the same tree of resolved fields.
+
+// without `cli` only markdown is rendered, which uses a few of the helpers below
+#![cfg_attr(not(feature = "cli"), allow(dead_code))]
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::{Collection, CompsiteMetadata, FieldMetadata, TypeDescriptor};
+
+#[cfg(feature = "cli")]
+pub mod env;
+#[cfg(feature = "cli")]
+pub mod html;
+#[cfg(feature = "cli")]
+pub mod json_schema;
+#[cfg(feature = "cli")]
+pub mod toml;
+#[cfg(feature = "cli")]
+pub mod yaml;
+
+/// stands for the user chosen key of an entry in a map of composites, i.e. `backends.<name>`
+pub(crate) const MAP_KEY_PLACEHOLDER: &str = "<name>";
+
+/// comment on keys the user has to set
+pub(crate) const REQUIRED_NOTE: &str = "REQUIRED: no default value";
+
+/// comment on nested tables of an `Option` type
+pub(crate) const OPTIONAL_SECTION_NOTE: &str = "OPTIONAL: the whole section can be left out";
+
+/// A field of a composite, with its type resolved against all collected items
+pub(crate) struct ConfigNode<'a> {
+    /// key of this node in its parent table
+    pub key: String,
+    pub field: &'a FieldMetadata,
+    pub kind: NodeKind<'a>,
+    /// in an `Option` table further up, so the key can be left out along with it
+    pub inside_optional: bool,
+}
+
+pub(crate) enum NodeKind<'a> {
+    /// a plain value, `enum_item` is set when the value is one of an enum's variants
+    Value {
+        enum_item: Option<&'a CompsiteMetadata>,
+    },
+    /// a nested composite, or a list or map of them when `collection` is set
+    Table {
+        compsite: &'a CompsiteMetadata,
+        children: Vec<ConfigNode<'a>>,
+        collection: Option<Collection>,
+    },
+    /// a composite that is already being expanded further up, i.e. `children: Vec<Rule>` in
+    /// `Rule`, its keys are documented at `target`
+    Recursive {
+        compsite: &'a CompsiteMetadata,
+        collection: Option<Collection>,
+        /// dotted path of the table with the same keys, empty for the root
+        target: Vec<String>,
+    },
+}
+
+impl ConfigNode<'_> {
+    /// doc of the field, or of the nested type if the field has none
+    pub fn doc(&self) -> &str {
+        let doc = self.field.doc.as_deref().unwrap_or_default();
+        match &self.kind {
+            NodeKind::Table { compsite, .. } | NodeKind::Recursive { compsite, .. }
+                if doc.trim().is_empty() =>
+            {
+                &compsite.doc
+            }
+            _ => doc,
+        }
+    }
+
+    /// dotted path of the keys inside this node, given the path of the table it is in, i.e.
+    /// `backends[]` for a list and `backends.<name>` for a map
+    pub fn inner_path(&self, path: &[String]) -> Vec<String> {
+        let collection = match &self.kind {
+            NodeKind::Table { collection, .. } | NodeKind::Recursive { collection, .. } => {
+                *collection
+            }
+            NodeKind::Value { .. } => None,
+        };
+        scope_path(path, &self.key, collection)
+    }
+
+    /// the key must be set for the whole config to be valid, unlike [`is_required`] which
+    /// only looks at the key within its own table
+    pub fn is_required(&self) -> bool {
+        !self.inside_optional && is_required(self.field)
+    }
+}
+
+/// path of the keys inside `key`, a table in `path`, see [`ConfigNode::inner_path`]
+pub(crate) fn scope_path(
+    path: &[String],
+    key: &str,
+    collection: Option<Collection>,
+) -> Vec<String> {
+    let mut path = path.to_vec();
+    match collection {
+        None => path.push(key.to_string()),
+        Some(Collection::List) => path.push(format!("{}[]", key)),
+        Some(Collection::Map) => {
+            path.push(key.to_string());
+            path.push(MAP_KEY_PLACEHOLDER.to_string());
+        }
+    }
+    path
+}
+
+/// `Recursive, each element has the same keys as `rules[]``
+pub(crate) fn recursive_note(
+    compsite: &CompsiteMetadata,
+    collection: Option<Collection>,
+    target: &[String],
+) -> String {
+    recursive_note_naming(&format!("`{}`", compsite.name), collection, target)
+}
+
+/// [`recursive_note`], with the top level type written as `name`, i.e. a link to its docs
+pub(crate) fn recursive_note_naming(
+    name: &str,
+    collection: Option<Collection>,
+    target: &[String],
+) -> String {
+    let subject = match collection {
+        None => "has",
+        Some(Collection::List) => "each element has",
+        Some(Collection::Map) => "each entry has",
+    };
+    if target.is_empty() {
+        format!(
+            "Recursive, {} the same keys as the top level {}",
+            subject, name
+        )
+    } else {
+        format!(
+            "Recursive, {} the same keys as `{}`",
+            subject,
+            target.join(".")
+        )
+    }
+}
+
+/// resolve every field of `compsite`, skipped fields are left out and flattened ones are
+/// merged into the same level
+pub(crate) fn build_tree<'a>(
+    compsite: &'a CompsiteMetadata,
+    items: &'a BTreeMap<String, CompsiteMetadata>,
+) -> Vec<ConfigNode<'a>> {
+    build_tree_inner(
+        compsite,
+        items,
+        &[],
+        false,
+        &mut vec![(compsite.qualified_name(), Vec::new())],
+    )
+}
+
+/// `ancestors` are the composites being expanded along with their paths, a type that contains
+/// itself becomes a [`NodeKind::Recursive`] instead of being expanded forever
+fn build_tree_inner<'a>(
+    compsite: &'a CompsiteMetadata,
+    items: &'a BTreeMap<String, CompsiteMetadata>,
+    path: &[String],
+    inside_optional: bool,
+    ancestors: &mut Vec<(String, Vec<String>)>,
+) -> Vec<ConfigNode<'a>> {
+    let mut nodes = Vec::new();
+    for (key, field) in &compsite.fields {
+        if field.skip {
+            continue;
+        }
+        let collection = field.ty.item_type().0;
+        let item = field
+            .resolved
+            .as_deref()
+            .and_then(|key| items.get_key_value(key));
+        let mut node = ConfigNode {
+            key: key.clone(),
+            field,
+            kind: NodeKind::Value {
+                // lists of enums are plain values
+                enum_item: item
+                    .map(|(_, item)| item)
+                    .filter(|item| item.is_enum() && collection.is_none()),
+            },
+            inside_optional,
+        };
+        let Some((inner_key, inner)) = item.filter(|(_, item)| !item.is_enum()) else {
+            nodes.push(node);
+            continue;
+        };
+        if let Some((_, target)) = ancestors.iter().find(|(key, _)| key == inner_key) {
+            node.kind = NodeKind::Recursive {
+                compsite: inner,
+                collection,
+                target: target.clone(),
+            };
+            nodes.push(node);
+            continue;
+        }
+
+        let flatten = field.flatten && collection.is_none();
+        let inner_path = if flatten {
+            path.to_vec()
+        } else {
+            scope_path(path, key, collection)
+        };
+        ancestors.push((inner_key.clone(), inner_path.clone()));
+        let children = build_tree_inner(
+            inner,
+            items,
+            &inner_path,
+            inside_optional || is_optional(field),
+            ancestors,
+        );
+        ancestors.pop();
+        if flatten {
+            nodes.extend(children);
+        } else {
+            node.kind = NodeKind::Table {
+                compsite: inner,
+                children,
+                collection,
+            };
+            nodes.push(node);
+        }
+    }
+    nodes
+}
+
+/// `Option<T>`, the key can always be left out
+pub(crate) fn is_optional(field: &FieldMetadata) -> bool {
+    // metadata from older derive macros has no `optional` flag
+    field.optional || field.ty.is_option()
+}
+
+/// the key must be set by the user, as there is no default to fall back to
+pub(crate) fn is_required(field: &FieldMetadata) -> bool {
+    !is_optional(field) && field.default.is_none() && !field.has_default
+}
+
+/// Broad category of a field's type, decides how defaults and placeholders are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueKind {
+    Bool,
+    Integer,
+    Float,
+    String,
+    Array,
+    Map,
+}
+
+pub(crate) fn value_kind(field: &FieldMetadata) -> ValueKind {
+    let ty = field.ty.unwrapped();
+    let name = match ty {
+        TypeDescriptor::Path { .. } => ty.name().unwrap_or_default(),
+        TypeDescriptor::Array { .. }
+        | TypeDescriptor::Slice { .. }
+        | TypeDescriptor::Tuple { .. } => return ValueKind::Array,
+        // `&str` is unwrapped already
+        TypeDescriptor::Reference { .. } | TypeDescriptor::Other { .. } => {
+            return ValueKind::String
+        }
+    };
+    match name {
+        "bool" => ValueKind::Bool,
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128"
+        | "isize" => ValueKind::Integer,
+        "f32" | "f64" => ValueKind::Float,
+        "Vec" | "VecDeque" | "HashSet" | "BTreeSet" => ValueKind::Array,
+        "HashMap" | "BTreeMap" => ValueKind::Map,
+        // paths, enums and anything unknown are written as strings
+        _ => ValueKind::String,
+    }
+}
+
+/// the default of `field` typed according to its rust type, falls back to a string when the
+/// default can't be parsed as such
+pub(crate) fn default_value(field: &FieldMetadata) -> Option<Value> {
+    let default = field.default.as_deref()?;
+    let parsed = match value_kind(field) {
+        ValueKind::Bool => default.parse::<bool>().ok().map(Value::from),
+        ValueKind::Integer => default
+            .parse::<i64>()
+            .ok()
+            .map(Value::from)
+            .or_else(|| default.parse::<u64>().ok().map(Value::from)),
+        ValueKind::Float => default.parse::<f64>().ok().map(Value::from),
+        ValueKind::Array | ValueKind::Map => serde_json::from_str(default).ok(),
+        ValueKind::String => None,
+    };
+    Some(parsed.unwrap_or_else(|| Value::from(default)))
+}
+
+/// an example value for fields without default, the first allowed value for enums
+pub(crate) fn placeholder_value(
+    field: &FieldMetadata,
+    enum_item: Option<&CompsiteMetadata>,
+) -> Value {
+    if let Some(first) = enum_item.and_then(|e| e.allowed_values_for(field).first().cloned()) {
+        return Value::from(first);
+    }
+    match value_kind(field) {
+        ValueKind::Bool => Value::from(false),
+        ValueKind::Integer => Value::from(0),
+        ValueKind::Float => Value::from(0.0),
+        ValueKind::String => Value::from(""),
+        ValueKind::Array => Value::Array(Vec::new()),
+        ValueKind::Map => Value::Object(Default::default()),
+    }
+}
+
+/// `Allowed values: "a", "b"` for enum typed values
+pub(crate) fn allowed_values_note(
+    field: &FieldMetadata,
+    enum_item: Option<&CompsiteMetadata>,
+) -> Option<String> {
+    enum_item.map(|e| {
+        let values: Vec<String> = e
+            .allowed_values_for(field)
+            .iter()
+            .map(|v| format!("{:?}", v))
+            .collect();
+        format!("Allowed values: {}", values.join(", "))
+    })
+}
@@ -0,0 +1,161 @@
+//! Render an example config file, with every key documented by `#` comments.
+//!
+//! Keys with a default are set to it, keys without one are commented out with a placeholder.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+use toml_edit::{Array, InlineTable, Key};
+
+use super::{
+    allowed_values_note, build_tree, default_value, is_optional, is_required, placeholder_value,
+    recursive_note, ConfigNode, NodeKind, MAP_KEY_PLACEHOLDER, OPTIONAL_SECTION_NOTE,
+    REQUIRED_NOTE,
+};
+use crate::{find_root_items, index_items, resolve::display_name, Collection, CompsiteMetadata};
+
+/// returns a key-value pair of filename and toml content, one per root
+pub fn render_toml(
+    items: Vec<CompsiteMetadata>,
+    required_roots: &Option<Vec<String>>,
+) -> Vec<(String, String)> {
+    let items = index_items(items);
+    find_root_items(&items, required_roots)
+        .into_iter()
+        .map(|root| (display_name(root, &items), compsite_to_toml(root, &items)))
+        .collect()
+}
+
+pub fn compsite_to_toml(
+    compsite: &CompsiteMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+) -> String {
+    let mut output = String::new();
+    push_comment(&mut output, &compsite.doc);
+    write_table(&mut output, &[], &build_tree(compsite, items));
+    output
+}
+
+fn write_table(output: &mut String, path: &[String], nodes: &[ConfigNode]) {
+    // no blank line right after a `[table]` header
+    let mut need_blank = path.is_empty() && !output.is_empty();
+    // plain values first, everything after a `[table]` header belongs to that table
+    for node in nodes {
+        if matches!(node.kind, NodeKind::Table { .. }) {
+            continue;
+        }
+        if need_blank {
+            output.push('\n');
+        }
+        need_blank = true;
+        push_comment(output, node.doc());
+        let key = Key::new(node.key.as_str()).display_repr().to_string();
+        match &node.kind {
+            NodeKind::Value { enum_item } => {
+                if let Some(note) = allowed_values_note(node.field, *enum_item) {
+                    push_comment(output, &note);
+                }
+                push_deprecation(output, node);
+                if is_required(node.field) {
+                    push_comment(output, REQUIRED_NOTE);
+                }
+                match default_value(node.field) {
+                    Some(default) => {
+                        output.push_str(&format!("{} = {}\n", key, to_toml_value(&default)))
+                    }
+                    None => output.push_str(&format!(
+                        "# {} = {}\n",
+                        key,
+                        to_toml_value(&placeholder_value(node.field, *enum_item))
+                    )),
+                }
+            }
+            NodeKind::Recursive {
+                compsite,
+                collection,
+                target,
+            } => {
+                push_comment(output, &recursive_note(compsite, *collection, target));
+                push_deprecation(output, node);
+                let empty = match collection {
+                    Some(Collection::List) => "[]",
+                    _ => "{}",
+                };
+                output.push_str(&format!("# {} = {}\n", key, empty));
+            }
+            NodeKind::Table { .. } => unreachable!("tables are written after plain values"),
+        }
+    }
+    for node in nodes {
+        if let NodeKind::Table {
+            children,
+            collection,
+            ..
+        } = &node.kind
+        {
+            let mut path = path.to_vec();
+            path.push(node.key.clone());
+            if !output.is_empty() {
+                output.push('\n');
+            }
+            push_comment(output, node.doc());
+            push_deprecation(output, node);
+            if is_optional(node.field) {
+                push_comment(output, OPTIONAL_SECTION_NOTE);
+            }
+            match collection {
+                None => output.push_str(&format!("[{}]\n", toml_header(&path))),
+                // one example element of the array of tables
+                Some(Collection::List) => output.push_str(&format!("[[{}]]\n", toml_header(&path))),
+                // one example entry, the name is up to the user
+                Some(Collection::Map) => {
+                    path.push(MAP_KEY_PLACEHOLDER.to_string());
+                    output.push_str(&format!("[{}]\n", toml_header(&path)));
+                }
+            }
+            write_table(output, &path, children);
+        }
+    }
+}
+
+fn toml_header(path: &[String]) -> String {
+    path.iter()
+        .map(|k| Key::new(k.as_str()).display_repr().to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn push_comment(output: &mut String, doc: &str) {
+    for line in doc.lines() {
+        if line.trim().is_empty() {
+            output.push_str("#\n");
+        } else {
+            output.push_str(&format!("# {}\n", line));
+        }
+    }
+}
+
+fn push_deprecation(output: &mut String, node: &ConfigNode) {
+    if let Some(deprecation) = node.field.deprecation() {
+        push_comment(output, &format!("WARNING: {}", deprecation));
+    }
+}
+
+fn to_toml_value(value: &Value) -> toml_edit::Value {
+    match value {
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64().unwrap_or_default().into(),
+        },
+        Value::String(s) => s.into(),
+        Value::Array(values) => values.iter().map(to_toml_value).collect::<Array>().into(),
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| (k.as_str(), to_toml_value(v)))
+            .collect::<InlineTable>()
+            .into(),
+        // toml has no null, an empty string is the closest
+        Value::Null => "".into(),
+    }
+}
@@ -0,0 +1,175 @@
+//! Render an example values file as nested YAML mappings, with every key documented by `#`
+//! comments.
+//!
+//! Scalars are written in JSON syntax, which is also valid YAML, so no quoting rules are needed.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use super::{
+    allowed_values_note, build_tree, default_value, is_optional, is_required, placeholder_value,
+    recursive_note, ConfigNode, NodeKind, MAP_KEY_PLACEHOLDER, OPTIONAL_SECTION_NOTE,
+    REQUIRED_NOTE,
+};
+use crate::{find_root_items, index_items, resolve::display_name, Collection, CompsiteMetadata};
+
+const INDENT: &str = "  ";
+
+/// returns a key-value pair of filename and yaml content, one per root
+pub fn render_yaml(
+    items: Vec<CompsiteMetadata>,
+    required_roots: &Option<Vec<String>>,
+) -> Vec<(String, String)> {
+    let items = index_items(items);
+    find_root_items(&items, required_roots)
+        .into_iter()
+        .map(|root| (display_name(root, &items), compsite_to_yaml(root, &items)))
+        .collect()
+}
+
+pub fn compsite_to_yaml(
+    compsite: &CompsiteMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+) -> String {
+    let mut output = String::new();
+    push_comment(&mut output, "", &compsite.doc);
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    write_mapping(&mut output, 0, &build_tree(compsite, items));
+    output
+}
+
+fn write_mapping(output: &mut String, depth: usize, nodes: &[ConfigNode]) {
+    let indent = INDENT.repeat(depth);
+    for node in nodes {
+        push_comment(output, &indent, node.doc());
+        let key = yaml_key(&node.key);
+        match &node.kind {
+            NodeKind::Value { enum_item } => {
+                if let Some(note) = allowed_values_note(node.field, *enum_item) {
+                    push_comment(output, &indent, &note);
+                }
+                if let Some(deprecation) = node.field.deprecation() {
+                    push_comment(output, &indent, &format!("WARNING: {}", deprecation));
+                }
+                if is_required(node.field) {
+                    push_comment(output, &indent, REQUIRED_NOTE);
+                }
+                if let Some(default) = default_value(node.field) {
+                    output.push_str(&format!("{}{}: {}\n", indent, key, yaml_value(&default)));
+                } else if is_optional(node.field) {
+                    output.push_str(&format!("{}# {}:\n", indent, key));
+                } else {
+                    // empty lists and maps for collections
+                    let placeholder = placeholder_value(node.field, *enum_item);
+                    output.push_str(&format!(
+                        "{}{}: {}\n",
+                        indent,
+                        key,
+                        yaml_value(&placeholder)
+                    ));
+                }
+            }
+            NodeKind::Recursive {
+                compsite,
+                collection,
+                target,
+            } => {
+                push_comment(
+                    output,
+                    &indent,
+                    &recursive_note(compsite, *collection, target),
+                );
+                if let Some(deprecation) = node.field.deprecation() {
+                    push_comment(output, &indent, &format!("WARNING: {}", deprecation));
+                }
+                match collection {
+                    Some(Collection::List) => output.push_str(&format!("{}{}: []\n", indent, key)),
+                    Some(Collection::Map) => output.push_str(&format!("{}{}: {{}}\n", indent, key)),
+                    None => output.push_str(&format!("{}# {}:\n", indent, key)),
+                }
+            }
+            NodeKind::Table {
+                children,
+                collection,
+                ..
+            } => {
+                if let Some(deprecation) = node.field.deprecation() {
+                    push_comment(output, &indent, &format!("WARNING: {}", deprecation));
+                }
+                if is_optional(node.field) {
+                    push_comment(output, &indent, OPTIONAL_SECTION_NOTE);
+                }
+                match collection {
+                    Some(Collection::List) if children.is_empty() => {
+                        output.push_str(&format!("{}{}: []\n", indent, key))
+                    }
+                    _ if children.is_empty() => {
+                        output.push_str(&format!("{}{}: {{}}\n", indent, key))
+                    }
+                    None => {
+                        output.push_str(&format!("{}{}:\n", indent, key));
+                        write_mapping(output, depth + 1, children);
+                    }
+                    // one example element, its first line starts with the `- ` marker
+                    Some(Collection::List) => {
+                        output.push_str(&format!("{}{}:\n", indent, key));
+                        let mut element = String::new();
+                        write_mapping(&mut element, depth + 2, children);
+                        let marker = format!("{}- ", INDENT.repeat(depth + 1));
+                        output.push_str(&marker);
+                        output.push_str(&element[marker.len()..]);
+                    }
+                    // one example entry, the name is up to the user
+                    Some(Collection::Map) => {
+                        output.push_str(&format!("{}{}:\n", indent, key));
+                        output.push_str(&format!(
+                            "{}{}:\n",
+                            INDENT.repeat(depth + 1),
+                            yaml_key(MAP_KEY_PLACEHOLDER)
+                        ));
+                        write_mapping(output, depth + 2, children);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn push_comment(output: &mut String, indent: &str, doc: &str) {
+    for line in doc.lines() {
+        if line.trim().is_empty() {
+            output.push_str(&format!("{}#\n", indent));
+        } else {
+            output.push_str(&format!("{}# {}\n", indent, line));
+        }
+    }
+}
+
+/// scalars that yaml would read as something other than a string
+const RESERVED: &[&str] = &[
+    "true", "false", "yes", "no", "on", "off", "y", "n", "null", "~",
+];
+
+/// plain keys are left as is, anything else is quoted
+fn yaml_key(key: &str) -> String {
+    let plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !key.starts_with('-')
+        && key.parse::<f64>().is_err()
+        && !RESERVED.contains(&key.to_ascii_lowercase().as_str());
+    if plain {
+        key.to_string()
+    } else {
+        Value::from(key).to_string()
+    }
+}
+
+/// json flow style is valid yaml
+fn yaml_value(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
@@ -0,0 +1,358 @@
+//! Match the types written in fields to the collected items, by crate and module path rather
+//! than by the bare name, so same-named types don't shadow each other.
+
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+
+use crate::{CompsiteMetadata, FieldMetadata, TypeDescriptor};
+
+/// What a type written in a field refers to
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Lookup<'a> {
+    /// key of the only matching item
+    Found(&'a str),
+    /// not a collected item, i.e. `String`
+    Missing,
+    /// keys of all items that match equally well
+    Ambiguous(Vec<&'a str>),
+}
+
+/// find the item `ty` refers to, when written in a field of `from`
+///
+/// the written path, i.e. `tls::Config` or `crate::tls::Config`, has to be a suffix of the
+/// item's qualified name, when there are still several candidates the ones from the same crate
+/// win, then the ones from the same module
+pub(crate) fn lookup<'a>(
+    items: &'a BTreeMap<String, CompsiteMetadata>,
+    from: &CompsiteMetadata,
+    ty: &TypeDescriptor,
+) -> Lookup<'a> {
+    let TypeDescriptor::Path { segments, .. } = ty else {
+        return Lookup::Missing;
+    };
+    let Some((name, written)) = segments.split_last() else {
+        return Lookup::Missing;
+    };
+    let written = absolute_path(from, written);
+
+    let mut candidates: Vec<(&str, &CompsiteMetadata)> = items
+        .iter()
+        .filter(|(_, item)| &item.name == name)
+        .filter(|(key, _)| key_path(key).ends_with(&written))
+        .map(|(key, item)| (key.as_str(), item))
+        .collect();
+    if candidates.len() > 1 && from.crate_name.is_some() {
+        narrow(&mut candidates, |item| item.crate_name == from.crate_name);
+    }
+    if candidates.len() > 1 {
+        narrow(&mut candidates, |item| {
+            item.crate_name == from.crate_name && item.module_path == from.module_path
+        });
+    }
+    match candidates.as_slice() {
+        [] => Lookup::Missing,
+        [(key, _)] => Lookup::Found(key),
+        _ => Lookup::Ambiguous(candidates.iter().map(|(key, _)| *key).collect()),
+    }
+}
+
+/// keep the candidates matching `pred`, unless none does
+fn narrow(
+    candidates: &mut Vec<(&str, &CompsiteMetadata)>,
+    pred: impl Fn(&CompsiteMetadata) -> bool,
+) {
+    if candidates.iter().any(|(_, item)| pred(item)) {
+        candidates.retain(|(_, item)| pred(item));
+    }
+}
+
+/// the module part of a written path with `crate`, `self` and `super` replaced, i.e.
+/// `super::Config` in `app::net` is `app::Config`
+fn absolute_path(from: &CompsiteMetadata, written: &[String]) -> Vec<String> {
+    let module: Vec<String> = from
+        .crate_name
+        .iter()
+        .chain(from.module_path.iter().filter(|path| !path.is_empty()))
+        .flat_map(|part| part.split("::"))
+        .map(String::from)
+        .collect();
+    match written.first().map(String::as_str) {
+        Some("crate") => from
+            .crate_name
+            .iter()
+            .cloned()
+            .chain(written[1..].iter().cloned())
+            .collect(),
+        Some("self" | "super") => {
+            let supers = written.iter().take_while(|part| *part == "super").count();
+            let rest = written[supers..].iter().skip_while(|part| *part == "self");
+            module[..module.len().saturating_sub(supers)]
+                .iter()
+                .chain(rest)
+                .cloned()
+                .collect()
+        }
+        _ => written.to_vec(),
+    }
+}
+
+/// the module part of an item key, i.e. `["app", "net"]` for `app::net::Config`
+fn key_path(key: &str) -> Vec<String> {
+    let mut parts: Vec<String> = key.split("::").map(String::from).collect();
+    parts.pop();
+    parts
+}
+
+/// fill [`FieldMetadata::resolved`] of every field, returns a description of every field
+/// whose type matches more than one item, such fields are left unresolved
+pub(crate) fn resolve_items(items: &mut BTreeMap<String, CompsiteMetadata>) -> Vec<String> {
+    let mut resolved: Vec<(String, Vec<Option<String>>)> = Vec::new();
+    let mut ambiguities = Vec::new();
+    for (key, item) in items.iter() {
+        let fields = all_fields(item).map(|(field_name, field)| {
+            if field.skip {
+                return None;
+            }
+            match lookup(items, item, field.ty.item_type().1) {
+                Lookup::Found(found) => Some(found.to_string()),
+                Lookup::Missing => None,
+                Lookup::Ambiguous(candidates) => {
+                    ambiguities.push(format!(
+                        "`{}.{}`: `{}` is ambiguous, could be {}",
+                        item.name,
+                        field_name,
+                        field.ty.item_type().1,
+                        candidates.iter().map(|c| format!("`{}`", c)).join(", ")
+                    ));
+                    None
+                }
+            }
+        });
+        resolved.push((key.clone(), fields.collect()));
+    }
+    for (key, fields) in resolved {
+        let item = items.get_mut(&key).unwrap();
+        for (field, resolved) in all_fields_mut(item).zip(fields) {
+            field.resolved = resolved;
+        }
+    }
+    ambiguities
+}
+
+/// fields of a struct, or of all variants of an enum
+fn all_fields(item: &CompsiteMetadata) -> impl Iterator<Item = (&String, &FieldMetadata)> {
+    item.fields
+        .iter()
+        .chain(item.variants.iter().flat_map(|v| v.fields.iter()))
+        .map(|(name, field)| (name, field))
+}
+
+fn all_fields_mut(item: &mut CompsiteMetadata) -> impl Iterator<Item = &mut FieldMetadata> {
+    item.fields
+        .iter_mut()
+        .chain(item.variants.iter_mut().flat_map(|v| v.fields.iter_mut()))
+        .map(|(_, field)| field)
+}
+
+/// how `item` is referred to in output, i.e. file names and headings: just its name, unless
+/// another item has the same name
+pub(crate) fn display_name(
+    item: &CompsiteMetadata,
+    items: &BTreeMap<String, CompsiteMetadata>,
+) -> String {
+    if items
+        .values()
+        .filter(|other| other.name == item.name)
+        .count()
+        > 1
+    {
+        item.qualified_name()
+    } else {
+        item.name.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// an item named after the last segment of `path`, i.e. `app::net::Server` is `Server` in
+    /// module `net` of crate `app`
+    fn item(path: &str) -> CompsiteMetadata {
+        let mut parts: Vec<&str> = path.split("::").collect();
+        let name = parts.pop().unwrap();
+        CompsiteMetadata {
+            name: name.to_string(),
+            crate_name: Some(parts[0].to_string()),
+            module_path: Some(parts[1..].join("::")),
+            ..Default::default()
+        }
+    }
+
+    fn items(paths: &[&str]) -> BTreeMap<String, CompsiteMetadata> {
+        paths
+            .iter()
+            .map(|path| (path.to_string(), item(path)))
+            .collect()
+    }
+
+    fn ty(written: &str) -> TypeDescriptor {
+        TypeDescriptor::Path {
+            segments: written.split("::").map(String::from).collect(),
+            args: Vec::new(),
+        }
+    }
+
+    /// `written` as the type of a field of `from`
+    fn find<'a>(
+        items: &'a BTreeMap<String, CompsiteMetadata>,
+        from: &str,
+        written: &str,
+    ) -> Lookup<'a> {
+        lookup(items, &items[from], &ty(written))
+    }
+
+    #[test]
+    fn qualified_names() {
+        let items = items(&[
+            "app::Config",
+            "app::net::Server",
+            "app::tls::Config",
+            "other::tls::Config",
+        ]);
+        let from = "app::net::Server";
+        assert_eq!(
+            find(&items, from, "tls::Config"),
+            Lookup::Found("app::tls::Config")
+        );
+        assert_eq!(
+            find(&items, from, "crate::tls::Config"),
+            Lookup::Found("app::tls::Config")
+        );
+        assert_eq!(
+            find(&items, from, "other::tls::Config"),
+            Lookup::Found("other::tls::Config")
+        );
+        assert_eq!(
+            find(&items, from, "super::Config"),
+            Lookup::Found("app::Config")
+        );
+        assert_eq!(
+            find(&items, from, "super::tls::Config"),
+            Lookup::Found("app::tls::Config")
+        );
+        assert_eq!(find(&items, from, "crate::net::Config"), Lookup::Missing);
+    }
+
+    #[test]
+    fn self_refers_to_the_module_of_the_field() {
+        let items = items(&["app::net::Server", "app::net::Tls", "app::Tls"]);
+        assert_eq!(
+            find(&items, "app::net::Server", "self::Tls"),
+            Lookup::Found("app::net::Tls")
+        );
+    }
+
+    #[test]
+    fn imported_name_from_another_module() {
+        // `use crate::tls::TlsConfig;` in `net`, the only `TlsConfig` anywhere
+        let items = items(&["app::net::Server", "app::tls::TlsConfig"]);
+        assert_eq!(
+            find(&items, "app::net::Server", "TlsConfig"),
+            Lookup::Found("app::tls::TlsConfig")
+        );
+    }
+
+    #[test]
+    fn imported_name_from_another_crate() {
+        // `use common::TlsConfig;` where only `common` has one
+        let items = items(&["app::Server", "common::TlsConfig"]);
+        assert_eq!(
+            find(&items, "app::Server", "TlsConfig"),
+            Lookup::Found("common::TlsConfig")
+        );
+    }
+
+    #[test]
+    fn same_module_wins_then_same_crate() {
+        let items = items(&[
+            "app::net::Server",
+            "app::net::Config",
+            "app::Config",
+            "app::Tls",
+            "common::Tls",
+        ]);
+        let from = "app::net::Server";
+        assert_eq!(
+            find(&items, from, "Config"),
+            Lookup::Found("app::net::Config")
+        );
+        assert_eq!(find(&items, from, "Tls"), Lookup::Found("app::Tls"));
+    }
+
+    #[test]
+    fn same_name_in_two_other_modules_is_ambiguous() {
+        // `use` statements are not recorded, so `use crate::tls::Config;` in `net` can't pick
+        let items = items(&["app::net::Server", "app::db::Config", "app::tls::Config"]);
+        assert_eq!(
+            find(&items, "app::net::Server", "Config"),
+            Lookup::Ambiguous(vec!["app::db::Config", "app::tls::Config"])
+        );
+    }
+
+    #[test]
+    fn same_name_in_two_other_crates_is_ambiguous() {
+        let items = items(&["app::Server", "common::Config", "db::Config"]);
+        assert_eq!(
+            find(&items, "app::Server", "Config"),
+            Lookup::Ambiguous(vec!["common::Config", "db::Config"])
+        );
+    }
+
+    #[test]
+    fn not_an_item() {
+        let items = items(&["app::Server"]);
+        assert_eq!(find(&items, "app::Server", "String"), Lookup::Missing);
+        let tuple = TypeDescriptor::Tuple { elems: Vec::new() };
+        assert_eq!(
+            lookup(&items, &items["app::Server"], &tuple),
+            Lookup::Missing
+        );
+    }
+
+    #[test]
+    fn ambiguous_fields_are_reported_and_left_unresolved() {
+        let mut items = items(&["app::db::Config", "app::tls::Config", "app::net::Tls"]);
+        let mut server = item("app::net::Server");
+        server.fields = vec![
+            (
+                "config".to_string(),
+                FieldMetadata {
+                    ty: ty("Config"),
+                    ..Default::default()
+                },
+            ),
+            (
+                "tls".to_string(),
+                FieldMetadata {
+                    ty: ty("Tls"),
+                    ..Default::default()
+                },
+            ),
+        ];
+        items.insert("app::net::Server".to_string(), server);
+
+        let ambiguities = resolve_items(&mut items);
+        assert_eq!(
+            ambiguities,
+            vec![
+                "`Server.config`: `Config` is ambiguous, could be `app::db::Config`, \
+                 `app::tls::Config`"
+            ]
+        );
+        let fields = &items["app::net::Server"].fields;
+        assert_eq!(fields[0].1.resolved, None);
+        assert_eq!(fields[1].1.resolved.as_deref(), Some("app::net::Tls"));
+    }
+}
@@ -0,0 +1,179 @@
+//! Collect metadata by parsing the workspace sources with `syn`, without running `cargo doc`.
+//!
+//! Much faster, but only sees types written in the sources, so types generated by other macros
+//! are missed, and a `#[cfg_attr(..., derive(Opt2Doc))]` counts whether its condition holds or
+//! not.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use opt2doc_parse::{get_compsite_metadata, module_path_from_file};
+use syn::{punctuated::Punctuated, Attribute, DeriveInput, Item, Meta, Token};
+
+use crate::{CompsiteMetadata, SourceLocation};
+
+/// Directories that never contain sources we care about
+const SKIPPED_DIRS: &[&str] = &["target", ".git"];
+
+/// Parse every `.rs` file under `repo` and collect all types with `#[derive(Opt2Doc)]`
+///
+/// Files that fail to parse and types with invalid attributes are reported and skipped,
+/// rustc would reject them anyway.
+pub fn scan_repo(repo: &Path) -> Vec<CompsiteMetadata> {
+    let mut files = Vec::new();
+    find_rust_files(repo, &mut files);
+    files.sort();
+
+    let mut ret = Vec::new();
+    for path in files {
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let file = match syn::parse_file(&content) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Failed to parse {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        let (package, module) = match find_package(&path) {
+            Some((name, root)) => (
+                Some(name),
+                path.strip_prefix(root)
+                    .map(module_path_from_file)
+                    .unwrap_or_default(),
+            ),
+            None => (None, String::new()),
+        };
+        let ctx = FileContext {
+            path: &path,
+            display_path: path.strip_prefix(repo).unwrap_or(&path),
+            crate_name: package.as_ref().map(|name| name.replace('-', "_")),
+            package,
+        };
+        scan_items(&ctx, &module, &file.items, &mut ret);
+    }
+    ret
+}
+
+/// Where the items of a file come from
+struct FileContext<'a> {
+    path: &'a Path,
+    /// relative to the scanned repo
+    display_path: &'a Path,
+    package: Option<String>,
+    crate_name: Option<String>,
+}
+
+/// name and root directory of the package `file` belongs to, from the closest `Cargo.toml`
+/// with a `[package]` table
+///
+/// binaries are not told apart from the library, they all get the package's name
+fn find_package(file: &Path) -> Option<(String, &Path)> {
+    file.ancestors().skip(1).find_map(|dir| {
+        let manifest = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+        let manifest: toml::Value = toml::from_str(&manifest).ok()?;
+        let name = manifest.get("package")?.get("name")?.as_str()?;
+        Some((name.to_string(), dir))
+    })
+}
+
+fn find_rust_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        if path.is_dir() {
+            if !SKIPPED_DIRS.iter().any(|skipped| name == *skipped) {
+                find_rust_files(&path, files);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+}
+
+/// also walks into inline `mod { ... }` blocks, `module` is the path of the module `items`
+/// are in
+fn scan_items(ctx: &FileContext, module: &str, items: &[Item], out: &mut Vec<CompsiteMetadata>) {
+    for item in items {
+        let input: DeriveInput = match item {
+            Item::Struct(s) if derives_opt2doc(&s.attrs) => s.clone().into(),
+            Item::Enum(e) if derives_opt2doc(&e.attrs) => e.clone().into(),
+            Item::Mod(m) => {
+                if let Some((_, items)) = &m.content {
+                    let inner = if module.is_empty() {
+                        m.ident.to_string()
+                    } else {
+                        format!("{}::{}", module, m.ident)
+                    };
+                    scan_items(ctx, &inner, items, out);
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        match get_compsite_metadata(&input) {
+            Ok(mut compsite) => {
+                eprintln!(
+                    " \u{001b}[1;93m Opt2Doc Scanned \u{001b}[0m {}",
+                    compsite.name
+                );
+                let start = input.ident.span().start();
+                compsite.package = ctx.package.clone();
+                compsite.crate_name = ctx.crate_name.clone();
+                compsite.module_path = Some(module.to_string());
+                compsite.source = Some(SourceLocation {
+                    file: ctx.display_path.to_string_lossy().into_owned(),
+                    line: start.line,
+                    column: start.column + 1,
+                });
+                out.push(compsite)
+            }
+            Err(e) => {
+                let start = e.span().start();
+                eprintln!(
+                    "{}:{}:{}: {}",
+                    ctx.path.display(),
+                    start.line,
+                    start.column + 1,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// `#[derive(Opt2Doc)]` or `#[derive(opt2doc_derive::Opt2Doc)]`, also behind
+/// `#[cfg_attr(feature = "...", derive(Opt2Doc))]` whatever the condition
+fn derives_opt2doc(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        let metas = if attr.path().is_ident("derive") {
+            vec![attr.meta.clone()]
+        } else if attr.path().is_ident("cfg_attr") {
+            attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .map(|metas| metas.into_iter().skip(1).collect())
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        metas.iter().any(|meta| match meta {
+            Meta::List(list) if list.path.is_ident("derive") => list
+                .parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)
+                .is_ok_and(|paths| {
+                    paths
+                        .iter()
+                        .any(|path| path.segments.last().is_some_and(|s| s.ident == "Opt2Doc"))
+                }),
+            _ => false,
+        })
+    })
+}
@@ -1,87 +1,21 @@
 use std::{
     collections::BTreeMap,
-    io::{ErrorKind, Read, Write},
+    io::{ErrorKind, Read},
     net::{SocketAddr, TcpListener, TcpStream},
 };
 
-use crate::CompsiteMetadata;
+use opt2doc_core::socket::{END_OF_PACKET, URL_ENV_VAR_NAME};
 
-/// End of a entire json packet this is ascii's EOT(End of Transmission) control character
-/// which should not appear in a json string
-pub const END_OF_PACKET: &str = "\u{0004}";
+use crate::{transport::MetadataReceiver, CompsiteMetadata};
 
-/// Socket use this env var to determine the url to connect to/ bind to
-pub const URL_ENV_VAR_NAME: &str = "OPT2DOC_URL";
-
-/// Default url to bind to
-pub const DEFAULT_URL: &str = "127.0.0.1:41503";
-
-pub fn get_socket_url() -> String {
-    if let Ok(url) = std::env::var(URL_ENV_VAR_NAME) {
-        url
-    } else {
-        DEFAULT_URL.to_string()
-    }
-}
-
-pub struct DocClientState {
-    conn: Option<TcpStream>,
-}
-
-impl Default for DocClientState {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl DocClientState {
-    /// Try to read options and connect to the server, if failed, further `send` call is ignored
-    pub fn new() -> Self {
-        let conn = {
-            // TODO: maybe diagnostic warnning
-            let path = get_socket_url();
-            match TcpStream::connect(path) {
-                Ok(conn) => {
-                    conn.set_nonblocking(true).unwrap();
-                    Some(conn)
-                }
-                Err(_) => None,
-            }
-        };
-        DocClientState { conn }
-    }
-    /// if the connection is made
-    pub fn is_connected(&self) -> bool {
-        self.conn.is_some()
-    }
-
-    pub fn try_send(&mut self, msg: String) {
-        if let Some(ref mut conn) = self.conn {
-            conn.write_all(msg.as_bytes()).unwrap();
-            conn.write_all(END_OF_PACKET.as_bytes()).unwrap();
-        }
-    }
-
-    /// send a new type to the server
-    pub fn try_insert_type(&mut self, compsite: CompsiteMetadata) {
-        if self.is_connected() {
-            println!(
-                " \u{001b}[1;93m Opt2Doc Documenting for \u{001b}[0m {}",
-                compsite.name
-            );
-        }
-        let out_str = serde_json::to_string_pretty(&compsite).unwrap();
-        self.try_send(format!("{}\n", out_str));
-    }
-}
-
-pub struct DocServerState {
+pub struct SocketServerState {
+    url: String,
     listener: TcpListener,
     max_id: usize,
     streams: BTreeMap<usize, (TcpStream, SocketAddr)>,
 }
 
-impl DocServerState {
+impl SocketServerState {
     /// create non-blocking socket listener
     ///
     /// panic when fail
@@ -89,6 +23,7 @@ impl DocServerState {
         let listener = TcpListener::bind(name).unwrap();
         listener.set_nonblocking(true).unwrap();
         Self {
+            url: name.to_string(),
             listener,
             max_id: 0,
             streams: Default::default(),
@@ -140,3 +75,20 @@ impl DocServerState {
         ret
     }
 }
+
+impl MetadataReceiver for SocketServerState {
+    fn envs(&self) -> Vec<(String, String)> {
+        vec![(URL_ENV_VAR_NAME.to_string(), self.url.clone())]
+    }
+
+    fn poll(&mut self) -> Vec<CompsiteMetadata> {
+        // first try to accept all incoming connections available
+        while self.try_accept().is_some() {}
+
+        self.try_recv()
+    }
+
+    fn finish(&mut self) -> Vec<CompsiteMetadata> {
+        self.poll()
+    }
+}
@@ -0,0 +1,33 @@
+//! How metadata gets from `doc_impl`, running inside rustdoc, back to `cargo opt2doc`.
+//!
+//! The client side, `opt2doc_core::DocClientState`, is picked by env vars set by `cargo opt2doc`,
+//! the server side by `--transport`.
+
+use opt2doc_core::socket::get_socket_url;
+
+use crate::{
+    args::Transport, jsonl::JsonlServerState, socket::SocketServerState, CompsiteMetadata,
+};
+
+impl Transport {
+    pub fn receiver(&self) -> Box<dyn MetadataReceiver> {
+        match self {
+            Transport::Socket => Box::new(SocketServerState::new(&get_socket_url())),
+            Transport::Jsonl => Box::new(JsonlServerState::new(JsonlServerState::per_run_path())),
+        }
+    }
+}
+
+/// Collects metadata sent by the derive macro while `cargo doc` runs
+pub trait MetadataReceiver {
+    /// env vars to pass to `cargo doc` so the client finds this receiver
+    fn envs(&self) -> Vec<(String, String)>;
+
+    /// called repeatedly while `cargo doc` is running, must never block
+    fn poll(&mut self) -> Vec<CompsiteMetadata> {
+        Vec::new()
+    }
+
+    /// called once after `cargo doc` exited
+    fn finish(&mut self) -> Vec<CompsiteMetadata>;
+}
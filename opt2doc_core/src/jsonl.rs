@@ -0,0 +1,48 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::{print_documenting, CompsiteMetadata};
+
+/// Path of the JSON Lines file to append metadata to, if set the file transport is used
+/// instead of the socket
+pub const JSONL_ENV_VAR_NAME: &str = "OPT2DOC_JSONL";
+
+/// Append each type as one line to a JSON Lines file
+pub struct JsonlClientState {
+    path: PathBuf,
+}
+
+impl JsonlClientState {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// append a new type to the file, errors are ignored just like a failed socket connection
+    pub fn try_insert_type(&mut self, compsite: CompsiteMetadata) {
+        if append_line(&self.path, &compsite).is_ok() {
+            print_documenting(&compsite);
+        }
+    }
+
+    /// append a json message, which may span several lines, as one line
+    pub fn try_send(&mut self, msg: &str) {
+        if let Ok(value) = serde_json::from_str::<serde_json::Value>(msg) {
+            let _ = append_line(&self.path, &value);
+        }
+    }
+}
+
+/// Several rustdoc processes may append to the same file concurrently, so each record is
+/// written with a single `write` on a file opened in append mode, which the OS won't
+/// interleave with other appends
+fn append_line(path: &Path, record: &impl Serialize) -> io::Result<()> {
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())
+}
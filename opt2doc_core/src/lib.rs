@@ -0,0 +1,352 @@
+//! Metadata collected by `#[derive(Opt2Doc)]`, shared by the derive macro, the runtime
+//! `opt2doc` crate and `cargo opt2doc`, along with the client sending it back while `cargo doc`
+//! runs.
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+pub mod jsonl;
+pub mod socket;
+mod transport;
+
+pub use transport::{print_documenting, DocClientState};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FieldMetadata {
+    pub name: Option<String>,
+    pub doc: Option<String>,
+    #[serde(deserialize_with = "deserialize_type")]
+    pub ty: TypeDescriptor,
+    pub default: Option<String>,
+    pub deprecated: Option<String>,
+    /// set by `#[opt2doc(skip)]`, skipped fields never show up in rendered output
+    #[serde(default)]
+    pub skip: bool,
+    /// set by `#[serde(flatten)]`, the fields of the inner type are placed on the same level
+    #[serde(default)]
+    pub flatten: bool,
+    /// the key can be omitted, i.e. `#[serde(default)]`, even if the default value is unknown
+    #[serde(default)]
+    pub has_default: bool,
+    /// long command line flag without the leading `--`, from clap's `#[arg(long)]`
+    #[serde(default)]
+    pub long: Option<String>,
+    /// short command line flag without the leading `-`, from clap's `#[arg(short)]`
+    #[serde(default)]
+    pub short: Option<char>,
+    /// env var the value can be read from, from clap's `#[arg(env)]`
+    #[serde(default)]
+    pub env: Option<String>,
+    /// placeholder for the value in `--help`, from clap's `#[arg(value_name)]`
+    #[serde(default)]
+    pub value_name: Option<String>,
+    /// the type is an `Option`, so the key can always be left out
+    #[serde(default)]
+    pub optional: bool,
+    /// the value is parsed with clap's `ValueEnum`, so enum variants are spelled as
+    /// [`VariantMetadata::cli_name`]
+    #[serde(default)]
+    pub value_enum: bool,
+    /// key of the item documenting the type of this field, i.e. `Backend` for `Vec<Backend>`,
+    /// filled in when items are indexed
+    #[serde(skip)]
+    pub resolved: Option<String>,
+}
+
+impl FieldMetadata {
+    /// human readable deprecation note, `None` if the field is not deprecated
+    pub fn deprecation(&self) -> Option<String> {
+        match self.deprecated.as_deref() {
+            None | Some("") => None,
+            Some("true") => Some("deprecated".to_string()),
+            Some(message) => Some(format!("deprecated, {}", message)),
+        }
+    }
+
+    /// all command line spellings of this field, i.e. `-o, --output <OUTPUT>`
+    pub fn cli_flags(&self) -> Option<String> {
+        let mut flags = Vec::new();
+        if let Some(short) = self.short {
+            flags.push(format!("-{}", short));
+        }
+        if let Some(long) = &self.long {
+            flags.push(format!("--{}", long));
+        }
+        if flags.is_empty() {
+            return None;
+        }
+        let mut flags = flags.join(", ");
+        if let Some(value_name) = &self.value_name {
+            flags.push_str(&format!(" <{}>", value_name));
+        }
+        Some(flags)
+    }
+}
+
+/// Structure of a field's rust type, i.e. `Option<Vec<InnerOpt>>` keeps its generic arguments
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TypeDescriptor {
+    /// `std::vec::Vec<T>`, `args` are the generic type arguments of the last segment
+    Path {
+        segments: Vec<String>,
+        #[serde(default)]
+        args: Vec<TypeDescriptor>,
+    },
+    /// `[T; N]`, `len` is the length expression as written
+    Array {
+        elem: Box<TypeDescriptor>,
+        len: String,
+    },
+    /// `[T]`
+    Slice { elem: Box<TypeDescriptor> },
+    /// `(A, B)`, the unit type is a tuple without elements
+    Tuple { elems: Vec<TypeDescriptor> },
+    /// `&T` or `&mut T`
+    Reference {
+        mutable: bool,
+        elem: Box<TypeDescriptor>,
+    },
+    /// anything else, i.e. trait objects or fn pointers, kept as written
+    Other { text: String },
+}
+
+impl Default for TypeDescriptor {
+    /// an empty path, which means the type is not known yet
+    fn default() -> Self {
+        Self::Path {
+            segments: Vec::new(),
+            args: Vec::new(),
+        }
+    }
+}
+
+impl TypeDescriptor {
+    /// a path type without generic arguments, i.e. `String`
+    pub fn named(name: &str) -> Self {
+        Self::Path {
+            segments: vec![name.to_string()],
+            args: Vec::new(),
+        }
+    }
+
+    /// last segment of a path type, which is also the name items are keyed by
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Path { segments, .. } => segments.last().map(String::as_str),
+            _ => None,
+        }
+    }
+
+    /// generic type arguments of a path type, i.e. `[K, V]` for `HashMap<K, V>`
+    pub fn args(&self) -> &[TypeDescriptor] {
+        match self {
+            Self::Path { args, .. } => args,
+            _ => &[],
+        }
+    }
+
+    /// strip smart pointers and `Option`, i.e. `Option<Box<InnerOpt>>` is `InnerOpt`
+    pub fn unwrapped(&self) -> &TypeDescriptor {
+        self.unwrap_once().map_or(self, TypeDescriptor::unwrapped)
+    }
+
+    /// an `Option` somewhere among the wrappers stripped by [`Self::unwrapped`]
+    pub fn is_option(&self) -> bool {
+        self.name() == Some("Option") || self.unwrap_once().is_some_and(TypeDescriptor::is_option)
+    }
+
+    fn unwrap_once(&self) -> Option<&TypeDescriptor> {
+        match (self, self.args()) {
+            (Self::Reference { elem, .. }, _) => Some(elem),
+            (_, [inner]) => match self.name()? {
+                "Option" | "Box" | "Arc" | "Rc" | "Cow" | "Cell" | "RefCell" | "Mutex"
+                | "RwLock" => Some(inner),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// the type a field is documented by, i.e. `Backend` for `Option<Vec<Arc<Backend>>>`,
+    /// along with the kind of collection it sits in
+    pub fn item_type(&self) -> (Option<Collection>, &TypeDescriptor) {
+        let ty = self.unwrapped();
+        match ty.collection() {
+            Some((collection, elem)) => (Some(collection), elem.unwrapped()),
+            None => (None, ty),
+        }
+    }
+
+    /// the element type of a list, or the value type of a map keyed by strings
+    pub fn collection(&self) -> Option<(Collection, &TypeDescriptor)> {
+        match self {
+            Self::Array { elem, .. } | Self::Slice { elem } => Some((Collection::List, elem)),
+            Self::Path { args, .. } => match (self.name()?, args.as_slice()) {
+                ("Vec" | "VecDeque" | "HashSet" | "BTreeSet" | "IndexSet", [elem]) => {
+                    Some((Collection::List, elem))
+                }
+                ("HashMap" | "BTreeMap" | "IndexMap", [_, value]) => Some((Collection::Map, value)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// How a collection of composites is laid out in a config file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collection {
+    /// `backends[].host`, an array of tables
+    List,
+    /// `backends.<name>.host`, a table of tables keyed by name
+    Map,
+}
+
+impl std::fmt::Display for TypeDescriptor {
+    /// written the way it is in rust source
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path { segments, args } => {
+                write!(f, "{}", segments.join("::"))?;
+                if !args.is_empty() {
+                    write!(f, "<{}>", args.iter().join(", "))?;
+                }
+                Ok(())
+            }
+            Self::Array { elem, len } => write!(f, "[{}; {}]", elem, len),
+            Self::Slice { elem } => write!(f, "[{}]", elem),
+            Self::Tuple { elems } if elems.len() == 1 => write!(f, "({},)", elems[0]),
+            Self::Tuple { elems } => write!(f, "({})", elems.iter().join(", ")),
+            Self::Reference { mutable, elem } => {
+                write!(f, "&{}{}", if *mutable { "mut " } else { "" }, elem)
+            }
+            Self::Other { text } => write!(f, "{}", text),
+        }
+    }
+}
+
+/// also accept the list of path segments older versions of the derive macro emit
+fn deserialize_type<'de, D>(deserializer: D) -> Result<TypeDescriptor, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Segments(Vec<String>),
+        Descriptor(TypeDescriptor),
+    }
+    Ok(match Repr::deserialize(deserializer)? {
+        Repr::Segments(segments) => TypeDescriptor::Path {
+            segments,
+            args: Vec::new(),
+        },
+        Repr::Descriptor(ty) => ty,
+    })
+}
+
+/// What kind of rust type a [`CompsiteMetadata`] is collected from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CompsiteKind {
+    #[default]
+    Struct,
+    Enum,
+}
+
+/// One variant of an enum, `fields` is empty for unit variants
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VariantMetadata {
+    pub name: String,
+    pub doc: String,
+    pub deprecated: Option<String>,
+    pub fields: Vec<(String, FieldMetadata)>,
+    #[serde(default)]
+    pub skip: bool,
+    /// spelling of the variant on the command line, used when the field is a clap `ValueEnum`
+    #[serde(default)]
+    pub cli_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompsiteMetadata {
+    pub name: String,
+    pub doc: String,
+    pub fields: Vec<(String, FieldMetadata)>,
+    #[serde(default)]
+    pub kind: CompsiteKind,
+    /// only non-empty when `kind` is [`CompsiteKind::Enum`]
+    #[serde(default)]
+    pub variants: Vec<VariantMetadata>,
+    /// set by `#[opt2doc(skip)]` on the type, such type is never rendered as a root
+    #[serde(default)]
+    pub skip: bool,
+    /// package the type is defined in, from `CARGO_PKG_NAME`
+    #[serde(default)]
+    pub package: Option<String>,
+    /// crate the type is defined in, from `CARGO_CRATE_NAME`
+    #[serde(default)]
+    pub crate_name: Option<String>,
+    /// module the type is defined in, relative to the crate root, i.e. `net::tls`
+    ///
+    /// the derive macro only knows the file, so inline `mod` blocks are left out there
+    #[serde(default)]
+    pub module_path: Option<String>,
+    /// where the type is defined
+    #[serde(default)]
+    pub source: Option<SourceLocation>,
+}
+
+/// Position of a type's name in the sources
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    /// relative to the workspace root
+    pub file: String,
+    pub line: usize,
+    /// 1-based
+    pub column: usize,
+}
+
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl CompsiteMetadata {
+    pub fn is_enum(&self) -> bool {
+        self.kind == CompsiteKind::Enum
+    }
+
+    /// `crate::module::Name`, which tells apart types with the same name, just the name if
+    /// the crate is unknown
+    pub fn qualified_name(&self) -> String {
+        [self.crate_name.as_deref(), self.module_path.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty())
+            .chain([self.name.as_str()])
+            .join("::")
+    }
+
+    /// names of all variants, i.e. the values a field of this type is allowed to take
+    pub fn allowed_values(&self) -> Vec<String> {
+        self.variants
+            .iter()
+            .filter(|v| !v.skip)
+            .map(|v| v.name.clone())
+            .collect()
+    }
+
+    /// like [`Self::allowed_values`], but spelled the way `field` accepts them
+    pub fn allowed_values_for(&self, field: &FieldMetadata) -> Vec<String> {
+        if !field.value_enum {
+            return self.allowed_values();
+        }
+        self.variants
+            .iter()
+            .filter(|v| !v.skip)
+            .map(|v| v.cli_name.clone().unwrap_or_else(|| v.name.clone()))
+            .collect()
+    }
+}
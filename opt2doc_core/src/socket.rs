@@ -0,0 +1,69 @@
+use std::{io::Write, net::TcpStream};
+
+use crate::{print_documenting, CompsiteMetadata};
+
+/// End of a entire json packet this is ascii's EOT(End of Transmission) control character
+/// which should not appear in a json string
+pub const END_OF_PACKET: &str = "\u{0004}";
+
+/// Socket use this env var to determine the url to connect to/ bind to
+pub const URL_ENV_VAR_NAME: &str = "OPT2DOC_URL";
+
+/// Default url to bind to
+pub const DEFAULT_URL: &str = "127.0.0.1:41503";
+
+pub fn get_socket_url() -> String {
+    if let Ok(url) = std::env::var(URL_ENV_VAR_NAME) {
+        url
+    } else {
+        DEFAULT_URL.to_string()
+    }
+}
+
+pub struct SocketClientState {
+    conn: Option<TcpStream>,
+}
+
+impl Default for SocketClientState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SocketClientState {
+    /// Try to read options and connect to the server, if failed, further `send` call is ignored
+    pub fn new() -> Self {
+        let conn = {
+            // TODO: maybe diagnostic warnning
+            let path = get_socket_url();
+            match TcpStream::connect(path) {
+                Ok(conn) => {
+                    conn.set_nonblocking(true).unwrap();
+                    Some(conn)
+                }
+                Err(_) => None,
+            }
+        };
+        SocketClientState { conn }
+    }
+    /// if the connection is made
+    pub fn is_connected(&self) -> bool {
+        self.conn.is_some()
+    }
+
+    pub fn try_send(&mut self, msg: String) {
+        if let Some(ref mut conn) = self.conn {
+            conn.write_all(msg.as_bytes()).unwrap();
+            conn.write_all(END_OF_PACKET.as_bytes()).unwrap();
+        }
+    }
+
+    /// send a new type to the server
+    pub fn try_insert_type(&mut self, compsite: CompsiteMetadata) {
+        if self.is_connected() {
+            print_documenting(&compsite);
+        }
+        let out_str = serde_json::to_string_pretty(&compsite).unwrap();
+        self.try_send(format!("{}\n", out_str));
+    }
+}
@@ -0,0 +1,64 @@
+//! Client side of the transport, picked by the env vars `cargo opt2doc` sets for `cargo doc`.
+
+use crate::{
+    jsonl::{JsonlClientState, JSONL_ENV_VAR_NAME},
+    socket::SocketClientState,
+    CompsiteMetadata,
+};
+
+/// Client used by `doc_impl`
+pub enum DocClientState {
+    Socket(SocketClientState),
+    Jsonl(JsonlClientState),
+}
+
+impl Default for DocClientState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DocClientState {
+    /// use the JSON Lines file if `OPT2DOC_JSONL` is set, otherwise try the socket
+    pub fn new() -> Self {
+        match std::env::var(JSONL_ENV_VAR_NAME) {
+            Ok(path) => Self::Jsonl(JsonlClientState::new(path)),
+            Err(_) => Self::Socket(SocketClientState::new()),
+        }
+    }
+
+    /// send a new type to `cargo opt2doc`
+    pub fn try_insert_type(&mut self, compsite: CompsiteMetadata) {
+        match self {
+            Self::Socket(client) => client.try_insert_type(compsite),
+            Self::Jsonl(client) => client.try_insert_type(compsite),
+        }
+    }
+
+    /// if the connection is made, a JSON Lines file is always written to
+    pub fn is_connected(&self) -> bool {
+        match self {
+            Self::Socket(client) => client.is_connected(),
+            Self::Jsonl(_) => true,
+        }
+    }
+
+    /// send a raw json message, dropped if not connected
+    #[deprecated(
+        since = "0.1.3",
+        note = "use `try_insert_type`, which writes the message the way the transport expects"
+    )]
+    pub fn try_send(&mut self, msg: String) {
+        match self {
+            Self::Socket(client) => client.try_send(msg),
+            Self::Jsonl(client) => client.try_send(&msg),
+        }
+    }
+}
+
+pub fn print_documenting(compsite: &CompsiteMetadata) {
+    println!(
+        " \u{001b}[1;93m Opt2Doc Documenting for \u{001b}[0m {}",
+        compsite.name
+    );
+}
@@ -1,18 +1,12 @@
-use darling::ast::NestedMeta;
-use darling::FromMeta;
-use opt2doc::DocClientState;
-use opt2doc::{CompsiteMetadata, FieldMetadata};
+use opt2doc_core::DocClientState;
+use opt2doc_core::{CompsiteMetadata, SourceLocation};
+use opt2doc_parse::{get_compsite_metadata, module_path_from_file};
 use proc_macro::TokenStream;
 use quote::quote;
 use quote::ToTokens;
-use std::collections::HashMap;
 use std::sync::Mutex;
-use syn::punctuated::Punctuated;
-use syn::Lit::{self};
-use syn::Meta::{self};
-use syn::Result;
-use syn::Token;
-use syn::{parse_macro_input, Attribute, Error, Expr, ExprLit, Field, MetaNameValue};
+use syn::{parse_macro_input, Field};
+
 /// options for the `opt2doc` derive macro
 static STATE: once_cell::sync::Lazy<Mutex<DocClientState>> =
     once_cell::sync::Lazy::new(|| Mutex::new(DocClientState::new()));
@@ -29,213 +23,157 @@ static STATE: once_cell::sync::Lazy<Mutex<DocClientState>> =
 /// where `rename` means the name of the
 /// option in the config file and `default` is the default value of the option.
 ///
-/// `type = "String"` is the type of the option and `doc` is the documentation of the option.
+/// `typ = "String"` is the type of the option and `doc` is the documentation of the option.
 ///
 /// `doc` is the docmuemntation of the option.
 ///
 /// if any of those is missing, this macro will try it's best to extract the information from the
 /// struct field definition.
+///
+/// For enums, every variant is documented with its name, doc comment and deprecation, `rename`
+/// and `doc` also work on variants.
+///
+/// `#[opt2doc(skip)]` on a field or variant hides it from the output, on a struct or enum it
+/// prevents the type from being rendered as a root, though it is still expanded where referenced.
+///
+/// `#[serde(...)]` attributes are also respected, so keys are documented the way serde
+/// deserializes them: `rename`, `rename_all`, `rename_all_fields`, `flatten`, `default` and
+/// `skip`/`skip_deserializing`. An explicit `#[opt2doc(rename = "...")]` still takes precedence.
+///
+/// clap's `#[arg(long, short, env, value_name, default_value, value_enum)]` attributes are
+/// harvested too, so there is no need to repeat e.g. `default_value` in `#[opt2doc(default)]`.
+///
+/// The crate, module and source location of the type are recorded as well, so same-named types
+/// are told apart, i.e. `tls: admin::Tls` next to `tls: Tls`. A type name that still matches
+/// more than one type is reported as ambiguous instead of picking one of them.
+///
+/// The type also implements `opt2doc::Opt2Doc`, so the metadata is available at runtime,
+/// which means the crate using this derive needs to depend on `opt2doc` as well.
 #[proc_macro_derive(Opt2Doc, attributes(opt2doc))]
 pub fn derive_doc(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as syn::DeriveInput);
 
-    let mut fields = Vec::new();
-    // let first deal with the struct fields
-    if let syn::Data::Struct(s) = input.data {
-        for field in s.fields {
-            // 1. read `opt2doc` attribute's key val pairs
-            let raw_doc = match get_attrs_from_field(&field) {
-                Ok(v) => v,
-                Err(e) => return e.to_compile_error().into(),
-            };
-            fields.push((raw_doc.name.clone().unwrap(), raw_doc));
-        }
-    }
-
-    let compsite = CompsiteMetadata {
-        name: input.ident.to_string(),
-        doc: get_doc_comment(&input.attrs),
-        fields,
+    let mut compsite = match get_compsite_metadata(&input) {
+        Ok(v) => v,
+        Err(e) => return e.to_compile_error().into(),
     };
+    set_identity(&mut compsite, &input.ident);
 
     let out_str = serde_json::to_string_pretty(&compsite).unwrap();
+    let trait_impl = impl_opt2doc_trait(&input, &out_str);
     // only generate doc if running `cargo doc`
     quote! {
         #[cfg(doc)]
         opt2doc_derive::doc_impl!(#out_str);
+
+        #trait_impl
     }
     .into()
 }
 
-/// User normally should never see this macro, since it should only run at `#[cfg(doc)]` and then remove itself
-/// after generating the documentation.
-#[proc_macro]
-pub fn doc_impl(input: TokenStream) -> TokenStream {
-    // unescape the input since it's a string that got escaped
-    let s: String = serde_json::from_str(&input.to_string()).unwrap();
-
-    let compsite: CompsiteMetadata = serde_json::from_str(&s).unwrap();
-
-    STATE.lock().unwrap().try_insert_type(compsite);
-    quote! {}.into()
-}
-
-fn get_attrs_from_field(field: &Field) -> Result<FieldMetadata> {
-    let mut doc = parse_opt2doc_attrs(field)?;
-    if doc.name.is_none() {
-        doc.name = Some(field.ident.as_ref().unwrap().to_string());
-    }
-
-    if doc.doc.is_none() {
-        doc.doc = Some(get_doc_comment(&field.attrs));
-    }
-
-    if doc.ty.is_empty() {
-        doc.ty = if let syn::Type::Path(t) = &field.ty {
-            t.path
-                .segments
-                .iter()
-                .map(|s| s.ident.to_string())
-                .collect()
-        } else {
-            return Err(Error::new_spanned(
-                field.ty.clone().into_token_stream(),
-                "type is expected to be a path",
-            ));
-        };
-    }
-
-    if doc.deprecated.is_none() {
-        doc.deprecated = Some(get_deprecated_comment(&field.attrs));
+/// record where the type is defined, so same-named types from different crates or modules are
+/// told apart
+fn set_identity(compsite: &mut CompsiteMetadata, ident: &syn::Ident) {
+    compsite.package = std::env::var("CARGO_PKG_NAME").ok();
+    compsite.crate_name = std::env::var("CARGO_CRATE_NAME")
+        .or_else(|_| std::env::var("CARGO_PKG_NAME").map(|name| name.replace('-', "_")))
+        .ok();
+
+    let span = ident.span().unwrap();
+    let Some(file) = span.local_file() else {
+        return;
+    };
+    // rustc is run from the workspace root, so relative paths are relative to it
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let absolute = cwd.join(&file);
+    if let Ok(manifest_dir) = std::env::var("CARGO_MANIFEST_DIR") {
+        if let Ok(relative) = absolute.strip_prefix(manifest_dir) {
+            compsite.module_path = Some(module_path_from_file(relative));
+        }
     }
-
-    Ok(doc)
+    let file = absolute.strip_prefix(&cwd).unwrap_or(&absolute);
+    compsite.source = Some(SourceLocation {
+        file: file.to_string_lossy().into_owned(),
+        line: span.line(),
+        column: span.column(),
+    });
 }
 
-/// a full example of all the attributes:
-///  
-/// `#[opt2doc(rename = "cfg_name", default="UTC", type="String", doc="The timezone of the system")]`
-fn parse_opt2doc_attrs(field: &Field) -> Result<FieldMetadata> {
-    // first get attribute with name of `opt2doc`
-    let mut doc = FieldMetadata::default();
-    let attr = if let Some(attr) = field
-        .attrs
-        .iter()
-        .find(|attr| attr.path().is_ident("opt2doc"))
-    {
-        attr
-    } else {
-        return Ok(FieldMetadata::default());
-    };
-    let attr_meta_list = if let Meta::List(list) = &attr.meta {
-        list
-    } else {
-        return Err(Error::new_spanned(attr, "expected #[opt2doc(...)]"));
+/// `impl opt2doc::Opt2Doc`, every type that shows up in a field is probed so nested derived
+/// types are linked through the trait
+fn impl_opt2doc_trait(input: &syn::DeriveInput, out_str: &str) -> proc_macro2::TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut probes: Vec<syn::Type> = Vec::new();
+    let fields: Vec<&Field> = match &input.data {
+        syn::Data::Struct(s) => s.fields.iter().collect(),
+        syn::Data::Enum(e) => e.variants.iter().flat_map(|v| v.fields.iter()).collect(),
+        syn::Data::Union(_) => Vec::new(),
     };
-    let meta_list = NestedMeta::parse_meta_list(attr_meta_list.tokens.clone())?;
+    for field in fields {
+        collect_probe_types(&field.ty, &mut probes);
+    }
 
-    let kv_pairs: HashMap<String, String> = HashMap::from_list(&meta_list)?;
+    quote! {
+        impl #impl_generics ::opt2doc::Opt2Doc for #name #ty_generics #where_clause {
+            fn metadata() -> ::opt2doc::CompsiteMetadata {
+                ::opt2doc::__private::with_module_path(
+                    ::opt2doc::__private::from_json(#out_str),
+                    ::std::module_path!(),
+                )
+            }
 
-    for (k, v) in kv_pairs.iter() {
-        match k.as_str() {
-            "rename" => doc.name = Some(v.clone()),
-            "default" => doc.default = Some(v.clone()),
-            "typ" => doc.ty = vec![v.clone()],
-            "doc" => doc.doc = Some(v.clone()),
-            _ => {}
+            fn collect_nested(out: &mut ::std::vec::Vec<::opt2doc::CompsiteMetadata>) {
+                #[allow(unused_imports)]
+                use ::opt2doc::__private::{ViaFallback as _, ViaOpt2Doc as _};
+                #((&&::opt2doc::__private::Probe::<#probes>::new()).collect_into(out);)*
+            }
         }
     }
-    Ok(doc)
 }
 
-/// Extracts the doc comment from the given attributes.
-fn get_doc_comment(attrs: &[Attribute]) -> String {
-    let comment_parts: Vec<_> = attrs
-        .iter()
-        .filter(|attr| attr.path().is_ident("doc"))
-        .filter_map(|attr| {
-            if let Meta::NameValue(MetaNameValue {
-                value:
-                    Expr::Lit(ExprLit {
-                        lit: Lit::Str(s), ..
-                    }),
-                ..
-            }) = attr.meta.clone()
-            {
-                Some(s.value())
-            } else {
-                // non #[doc = "..."] attributes are not our concern
-                // we leave them for rustc to handle
-                None
+/// the type itself and all types nested in it, i.e. `Option<Vec<Inner>>` gives
+/// `Option<Vec<Inner>>`, `Vec<Inner>` and `Inner`
+fn collect_probe_types(ty: &syn::Type, out: &mut Vec<syn::Type>) {
+    match ty {
+        syn::Type::Path(p) => {
+            let key = ty.to_token_stream().to_string();
+            if !out.iter().any(|t| t.to_token_stream().to_string() == key) {
+                out.push(ty.clone());
             }
-        })
-        .collect();
-    let mut lines: Vec<&str> = comment_parts
-        .iter()
-        .skip_while(|s| s.trim().is_empty())
-        .flat_map(|s| s.split('\n'))
-        .collect();
-    for line in lines.iter_mut() {
-        let trimmed = line.trim_start();
-        let trimmed = trimmed.trim_end();
-        *line = trimmed;
-    }
-
-    lines.join("\n")
-}
-
-/// Extracts the [`deprecated`] attribute from the given attributes.
-///
-/// Returns:
-/// - "true": if it's `#[deprecated]`
-/// - "message": if it's `#[deprecated = "message"]`
-/// - "since" and "note": if it's `#[deprecated(since = "version", note = "message")]`
-///
-/// [`deprecated`]: https://doc.rust-lang.org/reference/attributes/diagnostics.html#the-deprecated-attribute
-fn get_deprecated_comment(attrs: &[Attribute]) -> String {
-    let message_parts = attrs
-        .iter()
-        .filter(|attr| attr.path().is_ident("deprecated"))
-        .filter_map(|attr| match &attr.meta {
-            Meta::NameValue(MetaNameValue { value, .. }) => {
-                if let Expr::Lit(ExprLit {
-                    lit: Lit::Str(s), ..
-                }) = value
-                {
-                    Some(s.value())
-                } else {
-                    None
+            for segment in &p.path.segments {
+                if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
+                    for arg in &args.args {
+                        if let syn::GenericArgument::Type(inner) = arg {
+                            collect_probe_types(inner, out);
+                        }
+                    }
                 }
             }
-            Meta::List(list) => {
-                let mut since = String::new();
-                let mut note = String::new();
+        }
+        syn::Type::Reference(r) => collect_probe_types(&r.elem, out),
+        syn::Type::Array(a) => collect_probe_types(&a.elem, out),
+        syn::Type::Slice(s) => collect_probe_types(&s.elem, out),
+        syn::Type::Paren(p) => collect_probe_types(&p.elem, out),
+        syn::Type::Group(g) => collect_probe_types(&g.elem, out),
+        syn::Type::Tuple(t) => t
+            .elems
+            .iter()
+            .for_each(|elem| collect_probe_types(elem, out)),
+        _ => {}
+    }
+}
 
-                for nested in list
-                    .parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated)
-                    .ok()?
-                {
-                    let MetaNameValue { path, value, .. } = nested;
-                    let value = if let Expr::Lit(ExprLit {
-                        lit: Lit::Str(s), ..
-                    }) = value
-                    {
-                        s.value()
-                    } else {
-                        return None;
-                    };
-                    if path.is_ident("since") {
-                        since = format!("since: {}", value);
-                    } else if path.is_ident("note") {
-                        note = format!("note: {}", value);
-                    }
-                }
+/// User normally should never see this macro, since it should only run at `#[cfg(doc)]` and then remove itself
+/// after generating the documentation.
+#[proc_macro]
+pub fn doc_impl(input: TokenStream) -> TokenStream {
+    // unescape the input since it's a string that got escaped
+    let s: String = serde_json::from_str(&input.to_string()).unwrap();
 
-                Some(format!("{}, {}", since, note))
-            }
-            _ => Some("true".to_string()),
-        })
-        .collect::<Vec<_>>();
+    let compsite: CompsiteMetadata = serde_json::from_str(&s).unwrap();
 
-    message_parts.join("\n")
+    STATE.lock().unwrap().try_insert_type(compsite);
+    quote! {}.into()
 }
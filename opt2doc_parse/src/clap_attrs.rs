@@ -0,0 +1,200 @@
+//! Read clap's `#[arg(...)]`, `#[command(...)]` and `#[value(...)]` attributes, so command line
+//! structs document their flags, env vars and defaults without repeating them in `#[opt2doc]`.
+//!
+//! The legacy `#[clap(...)]` attribute is read the same way.
+
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::{Attribute, Expr, ExprLit, Ident, Lit, LitStr, Result, Token};
+
+use super::serde_attrs::{skip_meta_value, RenameRule};
+
+/// `#[command(rename_all, rename_all_env)]` on a struct, or `#[value(rename_all)]` on an enum
+#[derive(Debug, Clone)]
+pub struct ClapContainerAttrs {
+    /// rule for long flags and value names, `None` means `verbatim`
+    pub rename_all: Option<RenameRule>,
+    /// rule for env var names, `None` means `verbatim`
+    pub rename_all_env: Option<RenameRule>,
+}
+
+impl Default for ClapContainerAttrs {
+    /// clap's own defaults
+    fn default() -> Self {
+        Self {
+            rename_all: Some(RenameRule::Kebab),
+            rename_all_env: Some(RenameRule::ScreamingSnake),
+        }
+    }
+}
+
+/// Everything about a single field that clap knows
+#[derive(Debug, Clone, Default)]
+pub struct ClapFieldAttrs {
+    pub long: Option<String>,
+    pub short: Option<char>,
+    pub env: Option<String>,
+    pub value_name: Option<String>,
+    pub default_value: Option<String>,
+    /// bare `default_value_t`, the value is `Default::default()` which is unknown to us
+    pub has_default: bool,
+    pub value_enum: bool,
+    pub flatten: bool,
+    pub skip: bool,
+}
+
+/// `#[value(name, skip)]` on an enum variant
+#[derive(Debug, Clone, Default)]
+pub struct ClapVariantAttrs {
+    pub name: Option<String>,
+    pub skip: bool,
+}
+
+impl ClapVariantAttrs {
+    /// Name of the variant when used as a value on the command line
+    pub fn cli_name(&self, ident: &Ident, container: &ClapContainerAttrs) -> String {
+        let name = ident.unraw().to_string();
+        self.name.clone().unwrap_or_else(|| {
+            container
+                .rename_all
+                .map(|r| r.apply_to_variant(&name))
+                .unwrap_or(name)
+        })
+    }
+}
+
+fn is_clap_attr(attr: &Attribute, names: &[&str]) -> bool {
+    names.iter().any(|name| attr.path().is_ident(name))
+}
+
+pub fn parse_clap_container_attrs(attrs: &[Attribute]) -> Result<ClapContainerAttrs> {
+    let mut ret = ClapContainerAttrs::default();
+    for attr in attrs
+        .iter()
+        .filter(|attr| is_clap_attr(attr, &["command", "clap", "value"]))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                ret.rename_all = parse_rename_rule(&meta.value()?.parse()?)?;
+            } else if meta.path.is_ident("rename_all_env") {
+                ret.rename_all_env = parse_rename_rule(&meta.value()?.parse()?)?;
+            } else {
+                skip_meta_value(&meta)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(ret)
+}
+
+/// Flags given without a value, i.e. bare `long`, are derived from `ident` like clap does
+pub fn parse_clap_field_attrs(
+    attrs: &[Attribute],
+    ident: Option<&Ident>,
+    container: &ClapContainerAttrs,
+) -> Result<ClapFieldAttrs> {
+    let mut ret = ClapFieldAttrs::default();
+    let name = ident.map(|i| i.unraw().to_string()).unwrap_or_default();
+    let apply = |rule: Option<RenameRule>| {
+        rule.map(|r| r.apply_to_field(&name))
+            .unwrap_or(name.clone())
+    };
+
+    for attr in attrs
+        .iter()
+        .filter(|attr| is_clap_attr(attr, &["arg", "clap", "command"]))
+    {
+        attr.parse_nested_meta(|meta| {
+            let has_value = meta.input.peek(Token![=]);
+            if meta.path.is_ident("long") {
+                ret.long = Some(if has_value {
+                    expr_to_string(&meta.value()?.parse()?)
+                } else {
+                    apply(container.rename_all)
+                });
+            } else if meta.path.is_ident("short") {
+                ret.short = if has_value {
+                    match meta.value()?.parse()? {
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Char(c), ..
+                        }) => Some(c.value()),
+                        other => expr_to_string(&other).chars().next(),
+                    }
+                } else {
+                    apply(container.rename_all).chars().next()
+                };
+            } else if meta.path.is_ident("env") {
+                ret.env = Some(if has_value {
+                    expr_to_string(&meta.value()?.parse()?)
+                } else {
+                    apply(container.rename_all_env)
+                });
+            } else if meta.path.is_ident("value_name") {
+                ret.value_name = Some(expr_to_string(&meta.value()?.parse()?));
+            } else if meta.path.is_ident("default_value")
+                || meta.path.is_ident("default_value_t")
+                || meta.path.is_ident("default_value_os_t")
+            {
+                if has_value {
+                    ret.default_value = Some(expr_to_string(&meta.value()?.parse()?));
+                } else {
+                    ret.has_default = true;
+                }
+            } else if meta.path.is_ident("value_enum") {
+                ret.value_enum = true;
+            } else if meta.path.is_ident("flatten") {
+                ret.flatten = true;
+            } else if meta.path.is_ident("skip") {
+                skip_meta_value(&meta)?;
+                ret.skip = true;
+            } else if meta.path.is_ident("subcommand") {
+                // subcommands are parsed on their own, they are not options of this struct
+                ret.skip = true;
+            } else {
+                skip_meta_value(&meta)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(ret)
+}
+
+pub fn parse_clap_variant_attrs(attrs: &[Attribute]) -> Result<ClapVariantAttrs> {
+    let mut ret = ClapVariantAttrs::default();
+    for attr in attrs
+        .iter()
+        .filter(|attr| is_clap_attr(attr, &["value", "clap"]))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                ret.name = Some(expr_to_string(&meta.value()?.parse()?));
+            } else if meta.path.is_ident("skip") {
+                ret.skip = true;
+            } else {
+                skip_meta_value(&meta)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(ret)
+}
+
+/// clap accepts a few more spellings than serde, and `verbatim` to turn renaming off
+fn parse_rename_rule(lit: &LitStr) -> Result<Option<RenameRule>> {
+    Ok(match lit.value().as_str() {
+        "verbatim" | "Verbatim" => None,
+        "lower" => Some(RenameRule::Lower),
+        "upper" | "UPPER" => Some(RenameRule::Upper),
+        _ => Some(RenameRule::from_lit(lit)?),
+    })
+}
+
+/// String literals are unquoted, any other expression is kept as written
+fn expr_to_string(expr: &Expr) -> String {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        }) => s.value(),
+        other => other.to_token_stream().to_string(),
+    }
+}
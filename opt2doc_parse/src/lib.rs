@@ -0,0 +1,391 @@
+//! Turn the syntax tree of a type into [`CompsiteMetadata`].
+//!
+//! Used by the `Opt2Doc` derive macro and by the source scanner, which parses files with `syn`
+//! directly instead of running `cargo doc`.
+
+use clap_attrs::{
+    parse_clap_container_attrs, parse_clap_field_attrs, parse_clap_variant_attrs,
+    ClapContainerAttrs,
+};
+use darling::ast::NestedMeta;
+use darling::FromMeta;
+use quote::ToTokens;
+use serde_attrs::{parse_serde_attrs, RenameRule};
+use std::collections::HashMap;
+use std::path::{Component, Path};
+use syn::punctuated::Punctuated;
+use syn::Lit::{self};
+use syn::Meta::{self};
+use syn::Result;
+use syn::Token;
+use syn::{Attribute, Error, Expr, ExprLit, Field, Fields, MetaNameValue};
+
+use opt2doc_core::{
+    CompsiteKind, CompsiteMetadata, FieldMetadata, TypeDescriptor, VariantMetadata,
+};
+
+mod clap_attrs;
+mod serde_attrs;
+
+/// Build the metadata of a type with `#[derive(Opt2Doc)]`, shared by the derive macro and the
+/// source scanner so both produce the same output
+pub fn get_compsite_metadata(input: &syn::DeriveInput) -> Result<CompsiteMetadata> {
+    let attrs = parse_opt2doc_attrs(&input.attrs)?;
+    let serde = parse_serde_attrs(&input.attrs)?;
+    let clap = parse_clap_container_attrs(&input.attrs)?;
+    let mut compsite = CompsiteMetadata {
+        name: input.ident.to_string(),
+        doc: attrs.doc.unwrap_or_else(|| get_doc_comment(&input.attrs)),
+        skip: attrs.skip,
+        ..Default::default()
+    };
+    match &input.data {
+        syn::Data::Struct(s) => {
+            let ctx = FieldsContext {
+                rename_all: serde.rename_all,
+                all_default: serde.default,
+                clap,
+            };
+            compsite.fields = get_attrs_from_fields(&s.fields, &ctx)?;
+        }
+        syn::Data::Enum(e) => {
+            compsite.kind = CompsiteKind::Enum;
+            for variant in &e.variants {
+                compsite.variants.push(get_attrs_from_variant(
+                    variant,
+                    serde.rename_all,
+                    serde.rename_all_fields,
+                    &clap,
+                )?);
+            }
+        }
+        syn::Data::Union(u) => {
+            return Err(Error::new_spanned(
+                u.union_token,
+                "`Opt2Doc` can't be derived for unions",
+            ))
+        }
+    }
+    Ok(compsite)
+}
+
+/// `rename_all` is the enum's rule for variant names, `rename_all_fields` for the fields of
+/// struct variants
+fn get_attrs_from_variant(
+    variant: &syn::Variant,
+    rename_all: Option<RenameRule>,
+    rename_all_fields: Option<RenameRule>,
+    clap: &ClapContainerAttrs,
+) -> Result<VariantMetadata> {
+    let attrs = parse_opt2doc_attrs(&variant.attrs)?;
+    let serde = parse_serde_attrs(&variant.attrs)?;
+    let clap_variant = parse_clap_variant_attrs(&variant.attrs)?;
+    let ctx = FieldsContext {
+        rename_all: serde.rename_all.or(rename_all_fields),
+        ..Default::default()
+    };
+    Ok(VariantMetadata {
+        name: attrs
+            .name
+            .unwrap_or_else(|| serde.variant_name(&variant.ident, rename_all)),
+        doc: attrs.doc.unwrap_or_else(|| get_doc_comment(&variant.attrs)),
+        deprecated: Some(get_deprecated_comment(&variant.attrs)),
+        fields: get_attrs_from_fields(&variant.fields, &ctx)?,
+        skip: attrs.skip || serde.skip || clap_variant.skip,
+        cli_name: Some(clap_variant.cli_name(&variant.ident, clap)),
+    })
+}
+
+/// Container level attributes that affect how every field is documented
+#[derive(Default)]
+struct FieldsContext {
+    /// `#[serde(rename_all = "...")]`
+    rename_all: Option<RenameRule>,
+    /// `#[serde(default)]` on the container
+    all_default: bool,
+    clap: ClapContainerAttrs,
+}
+
+/// tuple fields are named by their index
+fn get_attrs_from_fields(
+    fields: &Fields,
+    ctx: &FieldsContext,
+) -> Result<Vec<(String, FieldMetadata)>> {
+    let mut ret = Vec::new();
+    for (idx, field) in fields.iter().enumerate() {
+        // 1. read `opt2doc` attribute's key val pairs
+        let mut raw_doc = get_attrs_from_field(field, ctx)?;
+        raw_doc.has_default |= ctx.all_default;
+        let name = raw_doc.name.get_or_insert_with(|| idx.to_string()).clone();
+        ret.push((name, raw_doc));
+    }
+    Ok(ret)
+}
+
+fn get_attrs_from_field(field: &Field, ctx: &FieldsContext) -> Result<FieldMetadata> {
+    let mut doc = parse_opt2doc_attrs(&field.attrs)?;
+    let serde = parse_serde_attrs(&field.attrs)?;
+    let clap = parse_clap_field_attrs(&field.attrs, field.ident.as_ref(), &ctx.clap)?;
+    if doc.name.is_none() {
+        doc.name = field
+            .ident
+            .as_ref()
+            .map(|ident| serde.field_name(ident, ctx.rename_all));
+    }
+    if doc.default.is_none() {
+        doc.default = clap.default_value;
+    }
+    doc.skip |= serde.skip || clap.skip;
+    doc.flatten = serde.flatten || clap.flatten;
+    doc.has_default = serde.default || clap.has_default;
+    doc.long = clap.long;
+    doc.short = clap.short;
+    if doc.env.is_none() {
+        doc.env = clap.env;
+    }
+    doc.value_name = clap.value_name;
+    doc.value_enum = clap.value_enum;
+
+    if doc.doc.is_none() {
+        doc.doc = Some(get_doc_comment(&field.attrs));
+    }
+
+    // `typ` only changes how the type is shown, the key is still optional if the field is
+    let ty = get_type_descriptor(&field.ty);
+    doc.optional = ty.is_option();
+    // clap flags and repeated options fall back to `false` and empty when not given
+    if (doc.long.is_some() || doc.short.is_some()) && matches!(ty.name(), Some("bool" | "Vec")) {
+        doc.has_default = true;
+    }
+    if doc.ty == TypeDescriptor::default() {
+        doc.ty = ty;
+    }
+
+    if doc.deprecated.is_none() {
+        doc.deprecated = Some(get_deprecated_comment(&field.attrs));
+    }
+
+    Ok(doc)
+}
+
+/// a type as spelled by the user, i.e. in `#[opt2doc(typ = "...")]`
+pub fn parse_type_str(s: &str) -> TypeDescriptor {
+    // not every spelling is a valid rust type, i.e. `<duration>`, keep those as is
+    match syn::parse_str::<syn::Type>(s) {
+        Ok(ty) => get_type_descriptor(&ty),
+        Err(_) => TypeDescriptor::named(s),
+    }
+}
+
+/// Describe a rust type, generic arguments, arrays, tuples and references are kept
+pub fn get_type_descriptor(ty: &syn::Type) -> TypeDescriptor {
+    match ty {
+        syn::Type::Path(t) if t.qself.is_none() => {
+            let segments = t
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            let args = match t.path.segments.last().map(|s| &s.arguments) {
+                Some(syn::PathArguments::AngleBracketed(generics)) => generics
+                    .args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        syn::GenericArgument::Type(ty) => Some(get_type_descriptor(ty)),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            TypeDescriptor::Path { segments, args }
+        }
+        syn::Type::Array(a) => TypeDescriptor::Array {
+            elem: Box::new(get_type_descriptor(&a.elem)),
+            len: a.len.to_token_stream().to_string(),
+        },
+        syn::Type::Slice(s) => TypeDescriptor::Slice {
+            elem: Box::new(get_type_descriptor(&s.elem)),
+        },
+        syn::Type::Tuple(t) => TypeDescriptor::Tuple {
+            elems: t.elems.iter().map(get_type_descriptor).collect(),
+        },
+        syn::Type::Reference(r) => TypeDescriptor::Reference {
+            mutable: r.mutability.is_some(),
+            elem: Box::new(get_type_descriptor(&r.elem)),
+        },
+        syn::Type::Paren(p) => get_type_descriptor(&p.elem),
+        syn::Type::Group(g) => get_type_descriptor(&g.elem),
+        other => TypeDescriptor::Other {
+            text: other.to_token_stream().to_string(),
+        },
+    }
+}
+
+/// Module of a source file, given its path relative to the package root, i.e. `net::tls` for
+/// `src/net/tls.rs` or `src/net/tls/mod.rs`, empty for crate roots like `src/lib.rs`
+pub fn module_path_from_file(file: &Path) -> String {
+    let parts: Vec<String> = file
+        .with_extension("")
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+    let modules = match parts.as_slice() {
+        // every binary, example and test is a crate of its own
+        ["src", "bin", _] | ["examples" | "tests" | "benches", _] => &[][..],
+        ["src", "bin", _, rest @ ..] | ["examples" | "tests" | "benches", _, rest @ ..] => rest,
+        ["src", rest @ ..] => rest,
+        rest => rest,
+    };
+    let modules = match modules {
+        [rest @ .., "lib" | "main" | "mod"] => rest,
+        modules => modules,
+    };
+    modules.join("::")
+}
+
+/// a full example of all the attributes:
+///  
+/// `#[opt2doc(rename = "cfg_name", default="UTC", typ="String", doc="The timezone of the system", env="TZ")]`
+///
+/// or `#[opt2doc(skip)]` to leave the item out of the generated documentation. `env` is the
+/// environment variable of the field, used as is instead of the one made from its key path.
+pub fn parse_opt2doc_attrs(attrs: &[Attribute]) -> Result<FieldMetadata> {
+    // first get attribute with name of `opt2doc`
+    let mut doc = FieldMetadata::default();
+    let attr = if let Some(attr) = attrs.iter().find(|attr| attr.path().is_ident("opt2doc")) {
+        attr
+    } else {
+        return Ok(FieldMetadata::default());
+    };
+    let attr_meta_list = if let Meta::List(list) = &attr.meta {
+        list
+    } else {
+        return Err(Error::new_spanned(attr, "expected #[opt2doc(...)]"));
+    };
+    let meta_list = NestedMeta::parse_meta_list(attr_meta_list.tokens.clone())?;
+
+    // flags like `skip` have no value, so pick them out before parsing key val pairs
+    let mut kv_list = Vec::new();
+    for meta in meta_list {
+        match &meta {
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => doc.skip = true,
+            _ => kv_list.push(meta),
+        }
+    }
+
+    let kv_pairs: HashMap<String, String> = HashMap::from_list(&kv_list)?;
+
+    for (k, v) in kv_pairs.iter() {
+        match k.as_str() {
+            "rename" => doc.name = Some(v.clone()),
+            "default" => doc.default = Some(v.clone()),
+            "typ" => doc.ty = parse_type_str(v),
+            "doc" => doc.doc = Some(v.clone()),
+            "env" => doc.env = Some(v.clone()),
+            _ => {
+                return Err(Error::new_spanned(
+                    attr_meta_list,
+                    format!("unknown opt2doc attribute `{}`", k),
+                ))
+            }
+        }
+    }
+    Ok(doc)
+}
+
+/// Extracts the doc comment from the given attributes.
+pub fn get_doc_comment(attrs: &[Attribute]) -> String {
+    let comment_parts: Vec<_> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| {
+            if let Meta::NameValue(MetaNameValue {
+                value:
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(s), ..
+                    }),
+                ..
+            }) = attr.meta.clone()
+            {
+                Some(s.value())
+            } else {
+                // non #[doc = "..."] attributes are not our concern
+                // we leave them for rustc to handle
+                None
+            }
+        })
+        .collect();
+    let mut lines: Vec<&str> = comment_parts
+        .iter()
+        .skip_while(|s| s.trim().is_empty())
+        .flat_map(|s| s.split('\n'))
+        .collect();
+    for line in lines.iter_mut() {
+        let trimmed = line.trim_start();
+        let trimmed = trimmed.trim_end();
+        *line = trimmed;
+    }
+
+    lines.join("\n")
+}
+
+/// Extracts the [`deprecated`] attribute from the given attributes.
+///
+/// Returns:
+/// - "true": if it's `#[deprecated]`
+/// - "message": if it's `#[deprecated = "message"]`
+/// - "since" and "note": if it's `#[deprecated(since = "version", note = "message")]`
+///
+/// [`deprecated`]: https://doc.rust-lang.org/reference/attributes/diagnostics.html#the-deprecated-attribute
+pub fn get_deprecated_comment(attrs: &[Attribute]) -> String {
+    let message_parts = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("deprecated"))
+        .filter_map(|attr| match &attr.meta {
+            Meta::NameValue(MetaNameValue { value, .. }) => {
+                if let Expr::Lit(ExprLit {
+                    lit: Lit::Str(s), ..
+                }) = value
+                {
+                    Some(s.value())
+                } else {
+                    None
+                }
+            }
+            Meta::List(list) => {
+                let mut since = String::new();
+                let mut note = String::new();
+
+                for nested in list
+                    .parse_args_with(Punctuated::<MetaNameValue, Token![,]>::parse_terminated)
+                    .ok()?
+                {
+                    let MetaNameValue { path, value, .. } = nested;
+                    let value = if let Expr::Lit(ExprLit {
+                        lit: Lit::Str(s), ..
+                    }) = value
+                    {
+                        s.value()
+                    } else {
+                        return None;
+                    };
+                    if path.is_ident("since") {
+                        since = format!("since: {}", value);
+                    } else if path.is_ident("note") {
+                        note = format!("note: {}", value);
+                    }
+                }
+
+                Some(format!("{}, {}", since, note))
+            }
+            _ => Some("true".to_string()),
+        })
+        .collect::<Vec<_>>();
+
+    message_parts.join("\n")
+}
@@ -0,0 +1,317 @@
+//! Read the `#[serde(...)]` attributes that change what keys serde accepts, so the documented
+//! keys are the ones that can actually be written in a config file.
+//!
+//! Only the deserialize side matters here, since the documentation is for config *input*.
+
+use syn::ext::IdentExt;
+use syn::meta::ParseNestedMeta;
+use syn::{Attribute, Error, Ident, LitStr, Result, Token};
+
+/// The subset of serde's container, variant and field attributes we care about
+#[derive(Debug, Clone, Default)]
+pub struct SerdeAttrs {
+    pub rename: Option<String>,
+    pub rename_all: Option<RenameRule>,
+    /// `rename_all_fields` on enums, applies to the fields of every struct variant
+    pub rename_all_fields: Option<RenameRule>,
+    pub flatten: bool,
+    pub default: bool,
+    pub skip: bool,
+}
+
+impl SerdeAttrs {
+    /// Name serde uses for a struct field or variant field
+    pub fn field_name(&self, ident: &Ident, rule: Option<RenameRule>) -> String {
+        let name = ident.unraw().to_string();
+        self.rename
+            .clone()
+            .unwrap_or_else(|| rule.map(|r| r.apply_to_field(&name)).unwrap_or(name))
+    }
+
+    /// Name serde uses for an enum variant
+    pub fn variant_name(&self, ident: &Ident, rule: Option<RenameRule>) -> String {
+        let name = ident.unraw().to_string();
+        self.rename
+            .clone()
+            .unwrap_or_else(|| rule.map(|r| r.apply_to_variant(&name)).unwrap_or(name))
+    }
+}
+
+/// Parse all `#[serde(...)]` attributes, unknown keys are ignored and left for serde to check
+pub fn parse_serde_attrs(attrs: &[Attribute]) -> Result<SerdeAttrs> {
+    let mut ret = SerdeAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                if let Some(name) = parse_deserialize_value(&meta)? {
+                    ret.rename = Some(name.value());
+                }
+            } else if meta.path.is_ident("rename_all") {
+                if let Some(rule) = parse_deserialize_value(&meta)? {
+                    ret.rename_all = Some(RenameRule::from_lit(&rule)?);
+                }
+            } else if meta.path.is_ident("rename_all_fields") {
+                if let Some(rule) = parse_deserialize_value(&meta)? {
+                    ret.rename_all_fields = Some(RenameRule::from_lit(&rule)?);
+                }
+            } else if meta.path.is_ident("flatten") {
+                ret.flatten = true;
+            } else if meta.path.is_ident("default") {
+                // `default` or `default = "path::to::fn"`
+                skip_meta_value(&meta)?;
+                ret.default = true;
+            } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                ret.skip = true;
+            } else {
+                skip_meta_value(&meta)?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(ret)
+}
+
+/// Handles both `key = "value"` and `key(serialize = "..", deserialize = "value")`,
+/// returns `None` if only the serialize side is given
+fn parse_deserialize_value(meta: &ParseNestedMeta) -> Result<Option<LitStr>> {
+    if meta.input.peek(Token![=]) {
+        return Ok(Some(meta.value()?.parse()?));
+    }
+    let mut ret = None;
+    meta.parse_nested_meta(|inner| {
+        let value: LitStr = inner.value()?.parse()?;
+        if inner.path.is_ident("deserialize") {
+            ret = Some(value);
+        }
+        Ok(())
+    })?;
+    Ok(ret)
+}
+
+/// Consume whatever follows a key we don't care about, i.e. `= expr` or `(...)`
+pub(crate) fn skip_meta_value(meta: &ParseNestedMeta) -> Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.input.parse::<proc_macro2::TokenTree>()?;
+    }
+    Ok(())
+}
+
+/// Same rules as serde's `rename_all`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    pub(crate) fn from_lit(lit: &LitStr) -> Result<Self> {
+        Ok(match lit.value().as_str() {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            "SCREAMING-KEBAB-CASE" => Self::ScreamingKebab,
+            other => {
+                return Err(Error::new_spanned(
+                    lit,
+                    format!("unknown rename rule `{}`", other),
+                ))
+            }
+        })
+    }
+
+    /// Variants are expected to be in `Pascal`
+    pub fn apply_to_variant(self, variant: &str) -> String {
+        match self {
+            Self::Pascal => variant.to_owned(),
+            Self::Lower => variant.to_ascii_lowercase(),
+            Self::Upper => variant.to_ascii_uppercase(),
+            Self::Camel => lowercase_first(variant),
+            Self::Snake => {
+                let mut snake = String::new();
+                for (i, ch) in variant.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            }
+            Self::ScreamingSnake => Self::Snake.apply_to_variant(variant).to_ascii_uppercase(),
+            Self::Kebab => Self::Snake.apply_to_variant(variant).replace('_', "-"),
+            Self::ScreamingKebab => Self::ScreamingSnake
+                .apply_to_variant(variant)
+                .replace('_', "-"),
+        }
+    }
+
+    /// Fields are expected to be in `snake_case`
+    pub fn apply_to_field(self, field: &str) -> String {
+        match self {
+            Self::Lower | Self::Snake => field.to_owned(),
+            Self::Upper | Self::ScreamingSnake => field.to_ascii_uppercase(),
+            Self::Pascal => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            }
+            Self::Camel => lowercase_first(&Self::Pascal.apply_to_field(field)),
+            Self::Kebab => field.replace('_', "-"),
+            Self::ScreamingKebab => Self::ScreamingSnake.apply_to_field(field).replace('_', "-"),
+        }
+    }
+}
+
+fn lowercase_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use quote::ToTokens;
+    use syn::parse_quote;
+
+    use super::*;
+
+    /// `(rename_all, field, variant)`, the field is `max_retry_count`, the variant `MaxRetryCount`
+    const RULES: &[(&str, &str, &str)] = &[
+        ("lowercase", "max_retry_count", "maxretrycount"),
+        ("UPPERCASE", "MAX_RETRY_COUNT", "MAXRETRYCOUNT"),
+        ("PascalCase", "MaxRetryCount", "MaxRetryCount"),
+        ("camelCase", "maxRetryCount", "maxRetryCount"),
+        ("snake_case", "max_retry_count", "max_retry_count"),
+        ("SCREAMING_SNAKE_CASE", "MAX_RETRY_COUNT", "MAX_RETRY_COUNT"),
+        ("kebab-case", "max-retry-count", "max-retry-count"),
+        ("SCREAMING-KEBAB-CASE", "MAX-RETRY-COUNT", "MAX-RETRY-COUNT"),
+    ];
+
+    #[test]
+    fn rename_all_rules() {
+        let field: Ident = parse_quote!(max_retry_count);
+        let variant: Ident = parse_quote!(MaxRetryCount);
+        for (rule, field_name, variant_name) in RULES {
+            let attrs = parse_serde_attrs(&[parse_quote!(#[serde(rename_all = #rule)])]).unwrap();
+            let rename_all = attrs.rename_all;
+            assert!(rename_all.is_some(), "{}", rule);
+            let none = SerdeAttrs::default();
+            assert_eq!(&none.field_name(&field, rename_all), field_name, "{}", rule);
+            assert_eq!(
+                &none.variant_name(&variant, rename_all),
+                variant_name,
+                "{}",
+                rule
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_rename_rule_is_an_error() {
+        let err =
+            parse_serde_attrs(&[parse_quote!(#[serde(rename_all = "Title Case")])]).unwrap_err();
+        assert_eq!(err.to_string(), "unknown rename rule `Title Case`");
+    }
+
+    #[test]
+    fn field_attrs() {
+        // `(attribute, name of the field `r#type` under `rename_all = "UPPERCASE"`)`
+        let cases: Vec<(Attribute, &str)> = vec![
+            (parse_quote!(#[doc = "no serde attribute"]), "TYPE"),
+            (parse_quote!(#[serde(rename = "kind")]), "kind"),
+            (parse_quote!(#[serde(rename(deserialize = "kind"))]), "kind"),
+            (
+                parse_quote!(#[serde(rename(serialize = "out", deserialize = "kind"))]),
+                "kind",
+            ),
+            // only renamed when serializing, so the rule still applies to the config key
+            (parse_quote!(#[serde(rename(serialize = "out"))]), "TYPE"),
+            (parse_quote!(#[serde(default, rename = "kind")]), "kind"),
+        ];
+        let ident: Ident = parse_quote!(r#type);
+        for (attr, name) in cases {
+            let attrs = parse_serde_attrs(std::slice::from_ref(&attr)).unwrap();
+            assert_eq!(
+                attrs.field_name(&ident, Some(RenameRule::Upper)),
+                name,
+                "{}",
+                attr.to_token_stream()
+            );
+        }
+    }
+
+    #[test]
+    fn flags() {
+        // `(attribute, flatten, default, skip)`
+        let cases: Vec<(Attribute, bool, bool, bool)> = vec![
+            (parse_quote!(#[serde(flatten)]), true, false, false),
+            (parse_quote!(#[serde(default)]), false, true, false),
+            (
+                parse_quote!(#[serde(default = "default_port")]),
+                false,
+                true,
+                false,
+            ),
+            (parse_quote!(#[serde(skip)]), false, false, true),
+            (
+                parse_quote!(#[serde(skip_deserializing)]),
+                false,
+                false,
+                true,
+            ),
+            // only skipped in the output, still read from the config
+            (
+                parse_quote!(#[serde(skip_serializing)]),
+                false,
+                false,
+                false,
+            ),
+            (
+                parse_quote!(#[serde(flatten, deserialize_with = "parse", bound(deserialize = "T: Default"))]),
+                true,
+                false,
+                false,
+            ),
+        ];
+        for (attr, flatten, default, skip) in cases {
+            let attrs = parse_serde_attrs(std::slice::from_ref(&attr)).unwrap();
+            let tokens = attr.to_token_stream();
+            assert_eq!(attrs.flatten, flatten, "{}", tokens);
+            assert_eq!(attrs.default, default, "{}", tokens);
+            assert_eq!(attrs.skip, skip, "{}", tokens);
+        }
+    }
+
+    #[test]
+    fn rename_all_fields_is_separate_from_rename_all() {
+        let attrs = parse_serde_attrs(&[parse_quote!(
+            #[serde(rename_all = "snake_case", rename_all_fields(deserialize = "camelCase"))]
+        )])
+        .unwrap();
+        assert_eq!(attrs.rename_all, Some(RenameRule::Snake));
+        assert_eq!(attrs.rename_all_fields, Some(RenameRule::Camel));
+    }
+}
@@ -0,0 +1,8 @@
+use opt2doc_derive::Opt2Doc;
+
+/// same name as `crate::Tls`, told apart by the module path
+#[derive(Debug, Opt2Doc)]
+pub struct Tls {
+    /// path to the client certificate admins must present
+    client_ca: String,
+}
@@ -4,11 +4,13 @@ use std::path::PathBuf;
 // TODO: use trybuild for macro test
 use opt2doc_derive::Opt2Doc;
 
+mod admin;
+
 fn main() {
-    println!("Hello, world!");
+    // metadata is also available at runtime through the `Opt2Doc` trait
+    println!("{}", <Opt as opt2doc::Opt2Doc>::markdown());
 }
 
-
 #[derive(Debug, Opt2Doc)]
 pub struct AnotherRoot {
     field1: bool,
@@ -25,9 +27,12 @@ pub struct Opt {
     id: usize,
     inner: InnerOpt,
     deprecated: Deprecated,
+    /// runtime only state, not part of the config
+    #[opt2doc(skip)]
+    cache: Vec<String>,
 }
 
-#[derive(Debug, Opt2Doc)]
+#[derive(Debug, Opt2Doc, serde::Deserialize)]
 pub struct InnerOpt {
     cfg: bool,
     ttl: usize,
@@ -42,3 +47,147 @@ pub struct Deprecated {
     #[deprecated(since = "0.1.1", note = "another deprecate message")]
     since_and_note: String,
 }
+
+#[derive(Debug, Opt2Doc)]
+pub struct WithMode {
+    /// How the server is run
+    mode: Mode,
+}
+
+/// Mode of the server
+#[derive(Debug, Opt2Doc)]
+pub enum Mode {
+    /// Run everything in one process
+    Standalone,
+    /// Join a cluster
+    Distributed { meta_addr: String, node_id: u64 },
+    #[opt2doc(rename = "legacy")]
+    #[deprecated(since = "0.1.1", note = "use `Standalone` instead")]
+    Old(String),
+}
+
+/// Helper type that is only ever used as part of another option
+#[derive(Debug, Opt2Doc)]
+#[opt2doc(skip)]
+pub struct NotARoot {
+    field: bool,
+}
+
+/// Keys are documented the way serde reads them
+#[derive(Debug, Opt2Doc, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SerdeOpt {
+    /// becomes `listen-addr`
+    listen_addr: String,
+    #[serde(rename = "TTL", default)]
+    time_to_live: usize,
+    #[serde(flatten)]
+    flattened: InnerOpt,
+    #[serde(skip)]
+    runtime_only: bool,
+    log_level: LogLevel,
+}
+
+#[derive(Debug, Opt2Doc, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Info,
+    DebugVerbose,
+}
+
+/// Command line flags are harvested from clap's attributes
+#[derive(Debug, Opt2Doc, clap::Parser)]
+#[command(rename_all_env = "SCREAMING_SNAKE_CASE")]
+pub struct CliOpt {
+    /// Where to write the output
+    #[arg(short, long, env, value_name = "DIR", default_value = "target/")]
+    output_dir: PathBuf,
+    #[arg(long = "log", default_value_t = 3)]
+    log_level: u8,
+    #[arg(long, value_enum)]
+    log_format: LogFormat,
+}
+
+#[derive(Debug, Clone, Opt2Doc, clap::ValueEnum)]
+pub enum LogFormat {
+    PlainText,
+    #[value(name = "json-lines")]
+    Json,
+}
+
+/// Rules can nest, metadata collection still terminates
+#[derive(Debug, Opt2Doc)]
+pub struct Rule {
+    pattern: String,
+    children: Vec<Rule>,
+    extra: Option<Box<InnerOpt>>,
+}
+
+#[derive(Debug, Opt2Doc)]
+pub struct Generic<T> {
+    value: T,
+}
+
+/// fields that are not plain paths
+#[derive(Debug, Opt2Doc)]
+pub struct Network {
+    /// address to bind to
+    #[opt2doc(default = "[0, 0, 0, 0]")]
+    addr: [u8; 4],
+    port_range: (u16, u16),
+    name: &'static str,
+    upstreams: Option<Vec<InnerOpt>>,
+    labels: std::collections::HashMap<String, String>,
+}
+
+/// a service with lists and maps of nested tables
+#[derive(Debug, Opt2Doc)]
+pub struct Service {
+    /// upstreams tried in order
+    backends: Vec<Backend>,
+    /// upstreams picked by name
+    named_backends: std::collections::BTreeMap<String, Backend>,
+    /// tls settings shared by all backends, left out to disable tls
+    default_tls: Option<std::sync::Arc<Tls>>,
+    mode: Option<Mode>,
+    /// read from the variable most hosting platforms set
+    #[opt2doc(env = "DATABASE_URL")]
+    database_url: String,
+}
+
+/// one upstream
+#[derive(Debug, Opt2Doc)]
+pub struct Backend {
+    /// host name or ip
+    host: String,
+    #[opt2doc(default = "80")]
+    port: u16,
+    tls: Tls,
+}
+
+#[derive(Debug, Opt2Doc)]
+pub struct Tls {
+    #[opt2doc(default = "false")]
+    enabled: bool,
+}
+
+/// references `Mutual`, which references it back
+#[derive(Debug, Opt2Doc)]
+pub struct Cyclic {
+    name: String,
+    other: Option<Box<Mutual>>,
+}
+
+#[derive(Debug, Opt2Doc)]
+pub struct Mutual {
+    #[opt2doc(default = "1")]
+    weight: u32,
+    back: Vec<Cyclic>,
+}
+
+/// two fields whose types are both named `Tls`
+#[derive(Debug, Opt2Doc)]
+pub struct Gateway {
+    tls: Tls,
+    admin_tls: admin::Tls,
+}